@@ -0,0 +1,267 @@
+use backup::BackupSet;
+use error::*;
+use runner::{CommandRunner, SystemRunner};
+use std::{
+    fs,
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+/// A command line to run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Environment variables set for the command, on top of raven's own
+    pub env: Vec<(String, String)>,
+}
+impl CommandSpec {
+    /// Creates a command for the given program without any arguments
+    pub fn new(program: impl Into<String>) -> CommandSpec {
+        CommandSpec {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+        }
+    }
+    /// Adds an argument
+    pub fn arg(mut self, arg: impl Into<String>) -> CommandSpec {
+        self.args.push(arg.into());
+        self
+    }
+    /// Sets an environment variable
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> CommandSpec {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+    /// Builds the std Command that runs this
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command.envs(self.env.iter().cloned());
+        command
+    }
+}
+/// A single side effect of applying a theme
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannedAction {
+    /// Creating a directory
+    CreateDir(String),
+    /// Copying a file into place
+    Copy {
+        from: String,
+        to: String,
+        overwrite: bool,
+    },
+    /// Writing assembled contents to a file
+    Write { path: String, overwrite: bool },
+    /// Removing a file
+    Remove(String),
+    /// Running a command and waiting for it to finish
+    Run(CommandSpec),
+    /// Starting a command in the background
+    Spawn(CommandSpec),
+    /// Setting a key in a settings file, like VSCode's settings.json
+    EditKey {
+        file: String,
+        key: String,
+        value: String,
+    },
+}
+/// Performs the side effects of loaders, or only records them when dry-running
+#[derive(Clone)]
+pub struct Executor {
+    planned: Option<Arc<Mutex<Vec<PlannedAction>>>>,
+    backup: Option<Arc<Mutex<BackupSet>>>,
+    runner: Arc<dyn CommandRunner>,
+}
+impl Executor {
+    /// Creates an executor that performs every action
+    pub fn new() -> Executor {
+        Executor {
+            planned: None,
+            backup: None,
+            runner: Arc::new(SystemRunner),
+        }
+    }
+    /// Creates an executor that only records actions
+    pub fn dry_run() -> Executor {
+        Executor {
+            planned: Some(Arc::new(Mutex::new(Vec::new()))),
+            backup: None,
+            runner: Arc::new(SystemRunner),
+        }
+    }
+    /// Runs commands through the given runner instead of spawning processes directly
+    pub fn with_runner<R>(mut self, runner: R) -> Executor
+    where
+        R: CommandRunner + 'static,
+    {
+        self.runner = Arc::new(runner);
+        self
+    }
+    /// The runner commands are run through
+    pub fn runner(&self) -> &dyn CommandRunner {
+        &*self.runner
+    }
+    /// Snapshots every file this executor changes into the given backup set
+    pub fn with_backup(mut self, set: BackupSet) -> Executor {
+        self.backup = Some(Arc::new(Mutex::new(set)));
+        self
+    }
+    /// Snapshots a file into the backup set, if there is one, before it's changed
+    pub fn snapshot(&self, path: impl Into<String>) -> Result<()> {
+        match self.backup {
+            Some(ref set) => set.lock().unwrap().snapshot(path),
+            None => Ok(()),
+        }
+    }
+    /// Whether actions are only being recorded
+    pub fn is_dry_run(&self) -> bool {
+        self.planned.is_some()
+    }
+    /// All actions recorded so far
+    pub fn planned(&self) -> Vec<PlannedAction> {
+        match self.planned {
+            Some(ref planned) => planned.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+    /// Records an action if dry-running, returning whether it was recorded. If it wasn't, the caller should perform it.
+    pub fn record(&self, action: PlannedAction) -> bool {
+        match self.planned {
+            Some(ref planned) => {
+                info!("Planned action {:?}", action);
+                planned.lock().unwrap().push(action);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Creates a directory and its parents if it doesn't exist yet
+    pub fn create_dir(&self, path: impl Into<String>) -> Result<()> {
+        let path = path.into();
+        if fs::metadata(&path).is_ok() || self.record(PlannedAction::CreateDir(path.clone())) {
+            return Ok(());
+        }
+        info!("Creating dir {}", path);
+        fs::create_dir_all(&path)?;
+        Ok(())
+    }
+    /// Copies a file, overwriting the destination
+    pub fn copy(&self, from: impl Into<String>, to: impl Into<String>) -> Result<()> {
+        let (from, to) = (from.into(), to.into());
+        let overwrite = fs::metadata(&to).is_ok();
+        if self.record(PlannedAction::Copy {
+            from: from.clone(),
+            to: to.clone(),
+            overwrite,
+        }) {
+            return Ok(());
+        }
+        self.snapshot(to.as_str())?;
+        info!("Copying {} to {}", from, to);
+        fs::copy(&from, &to)?;
+        Ok(())
+    }
+    /// Replaces a file with the given contents, creating it with the given permissions
+    pub fn write(&self, path: impl Into<String>, contents: &str, mode: u32) -> Result<()> {
+        let path = path.into();
+        let overwrite = fs::metadata(&path).is_ok();
+        if self.record(PlannedAction::Write {
+            path: path.clone(),
+            overwrite,
+        }) {
+            return Ok(());
+        }
+        self.snapshot(path.as_str())?;
+        if overwrite {
+            info!("Removing old {}", path);
+            fs::remove_file(&path)?;
+        }
+        info!("Creating and writing to {}", path);
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .mode(mode)
+            .open(&path)?
+            .write_all(contents.as_bytes())?;
+        Ok(())
+    }
+    /// Removes a file if it exists
+    pub fn remove(&self, path: impl Into<String>) -> Result<()> {
+        let path = path.into();
+        if fs::metadata(&path).is_err() || self.record(PlannedAction::Remove(path.clone())) {
+            return Ok(());
+        }
+        self.snapshot(path.as_str())?;
+        info!("Removing {}", path);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+    /// Runs a command, waiting for it to finish
+    pub fn run(&self, spec: CommandSpec) -> Result<()> {
+        if self.record(PlannedAction::Run(spec.clone())) {
+            return Ok(());
+        }
+        info!("Running {:?}", spec);
+        let out = self.runner.output(&spec)?;
+        if !out.success() {
+            warn!("{} exited with {:?}", spec.program, out.status);
+        }
+        Ok(())
+    }
+    /// Starts a command in the background, discarding its output. Returns its pid, or None when dry-running.
+    pub fn spawn(&self, spec: CommandSpec) -> Result<Option<u32>> {
+        if self.record(PlannedAction::Spawn(spec.clone())) {
+            return Ok(None);
+        }
+        info!("Spawning {:?}", spec);
+        Ok(Some(self.runner.spawn(&spec)?))
+    }
+}
+impl Default for Executor {
+    fn default() -> Executor {
+        Executor::new()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runner::RecordingRunner;
+    #[test]
+    fn runs_commands_through_runner() {
+        let runner = RecordingRunner::new();
+        let executor = Executor::new().with_runner(runner.clone());
+        executor
+            .run(CommandSpec::new("xrdb").arg("-merge").arg("xres"))
+            .unwrap();
+        assert!(runner.ran("xrdb", &["-merge", "xres"]));
+        assert!(executor.planned().is_empty());
+    }
+    #[test]
+    fn dry_run_records_instead_of_running() {
+        let runner = RecordingRunner::new();
+        let executor = Executor::dry_run().with_runner(runner.clone());
+        let spec = CommandSpec::new("polybar").arg("main");
+        executor.run(spec.clone()).unwrap();
+        assert_eq!(executor.spawn(spec.clone()).unwrap(), None);
+        executor
+            .write("/nonexistent/ravenlib/config", "contents", 0o666)
+            .unwrap();
+        assert!(runner.commands().is_empty());
+        assert_eq!(
+            executor.planned(),
+            vec![
+                PlannedAction::Run(spec.clone()),
+                PlannedAction::Spawn(spec),
+                PlannedAction::Write {
+                    path: String::from("/nonexistent/ravenlib/config"),
+                    overwrite: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use actions::Executor;
+use error::*;
+use paths::RavenPaths;
+use std::{
+    fs,
+    fs::OpenOptions,
+    io::{Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+/// A single file that was backed up before being changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupEntry {
+    /// The path of the changed file
+    pub path: String,
+    /// Name of the copy inside the backup set, or None if the file didn't exist before
+    pub backup: Option<String>,
+}
+/// Description of a backup set, stored in its backup.json
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupManifest {
+    /// Name of the backup set's directory
+    pub id: String,
+    /// The theme whose application caused the backup
+    pub theme: String,
+    /// Seconds since the unix epoch when the set was created
+    pub created: u64,
+    pub entries: Vec<BackupEntry>,
+}
+/// The snapshots of all files changed by a single run of a theme
+#[derive(Debug)]
+pub struct BackupSet {
+    dir: String,
+    /// Raven's root directory, whose files aren't backed up
+    root: String,
+    manifest: BackupManifest,
+}
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+impl BackupSet {
+    /// Starts a new backup set for the given theme, first removing the oldest sets so that at most `keep` remain along with the new one. Its directory is only created once a file is backed up. Returns None if `keep` is 0, which turns backups off.
+    pub fn new(paths: &RavenPaths, theme: impl Into<String>, keep: usize) -> Option<BackupSet> {
+        if keep == 0 {
+            return None;
+        }
+        if let Err(e) = prune_backups_in(paths, keep - 1) {
+            warn!("Couldn't remove old backup sets: {}", e);
+        }
+        let created = now();
+        let mut id = created.to_string();
+        let mut i = 1;
+        while fs::metadata(paths.file("backups") + "/" + &id).is_ok() {
+            id = created.to_string() + "-" + &i.to_string();
+            i += 1;
+        }
+        Some(BackupSet {
+            dir: paths.file("backups") + "/" + &id,
+            root: paths.root.clone(),
+            manifest: BackupManifest {
+                id,
+                theme: theme.into(),
+                created,
+                entries: Vec::new(),
+            },
+        })
+    }
+    /// Snapshots a file before it is changed. Files are only snapshotted the first time they're changed within a set, and raven's own files, like rendered options, not at all.
+    pub fn snapshot(&mut self, path: impl Into<String>) -> Result<()> {
+        let path = path.into();
+        if path.starts_with(&(self.root.clone() + "/"))
+            || self.manifest.entries.iter().any(|x| x.path == path)
+        {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let mut backup = None;
+        if fs::metadata(&path).is_ok() {
+            let name = self.manifest.entries.len().to_string();
+            info!("Backing up {} to {}/{}", path, self.dir, name);
+            fs::copy(&path, self.dir.clone() + "/" + &name)?;
+            backup = Some(name);
+        }
+        self.manifest.entries.push(BackupEntry { path, backup });
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.dir.clone() + "/backup.json")?
+            .write_all(serde_json::to_string(&self.manifest)?.as_bytes())?;
+        Ok(())
+    }
+    /// The set's manifest
+    pub fn manifest(&self) -> &BackupManifest {
+        &self.manifest
+    }
+}
+/// Lists all stored backup sets, oldest first
+pub fn list_backups() -> Result<Vec<BackupManifest>> {
+    list_backups_in(&RavenPaths::try_new()?)
+}
+/// Lists all backup sets stored under the given paths, oldest first
+pub fn list_backups_in(paths: &RavenPaths) -> Result<Vec<BackupManifest>> {
+    let mut sets = Vec::new();
+    if fs::metadata(paths.file("backups")).is_err() {
+        return Ok(sets);
+    }
+    for entry in fs::read_dir(paths.file("backups"))? {
+        let manifest = entry?.path().join("backup.json");
+        if fs::metadata(&manifest).is_err() {
+            continue;
+        }
+        let mut st = String::new();
+        fs::File::open(manifest)?.read_to_string(&mut st)?;
+        sets.push(serde_json::from_str::<BackupManifest>(&st)?);
+    }
+    sets.sort_by(|a, b| (a.created, a.id.len(), &a.id).cmp(&(b.created, b.id.len(), &b.id)));
+    Ok(sets)
+}
+/// Removes the oldest backup sets under the given paths, keeping the newest `keep`. Returns how many were removed.
+pub fn prune_backups_in(paths: &RavenPaths, keep: usize) -> Result<usize> {
+    let sets = list_backups_in(paths)?;
+    let old = sets.len().saturating_sub(keep);
+    for set in sets.iter().take(old) {
+        info!("Removing old backup set {}", set.id);
+        fs::remove_dir_all(paths.file("backups") + "/" + &set.id)?;
+    }
+    Ok(old)
+}
+/// Restores the files changed by the last `last_n` theme runs, newest first. Returns how many backup sets were restored.
+pub fn rollback(last_n: usize) -> Result<usize> {
+    rollback_in(&RavenPaths::try_new()?, last_n)
+}
+/// Restores the files changed by the last `last_n` theme runs under the given paths
+pub fn rollback_in(paths: &RavenPaths, last_n: usize) -> Result<usize> {
+    rollback_with(paths, &Executor::new(), last_n)
+}
+/// Restores the files changed by the last `last_n` theme runs under the given paths through an executor. When dry-running, the backup sets are kept.
+pub fn rollback_with(paths: &RavenPaths, executor: &Executor, last_n: usize) -> Result<usize> {
+    let sets = list_backups_in(paths)?;
+    let mut restored = 0;
+    for set in sets.iter().rev().take(last_n) {
+        info!("Rolling back backup set {} of theme {}", set.id, set.theme);
+        let dir = paths.file("backups") + "/" + &set.id;
+        for entry in set.entries.iter().rev() {
+            match entry.backup {
+                Some(ref name) => {
+                    info!("Restoring {}", entry.path);
+                    executor.copy(dir.clone() + "/" + name, entry.path.as_str())?;
+                }
+                None => {
+                    info!("Removing {}, which didn't exist before", entry.path);
+                    executor.remove(entry.path.as_str())?;
+                }
+            }
+        }
+        if !executor.is_dry_run() {
+            info!("Removing backup set {}", set.id);
+            fs::remove_dir_all(dir)?;
+        }
+        restored += 1;
+    }
+    Ok(restored)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    /// Creates a home with an empty raven root in the temp directory
+    fn test_paths(name: &str) -> RavenPaths {
+        let home =
+            env::temp_dir().join(format!("ravenlib-backup-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        fs::create_dir_all(&paths.root).unwrap();
+        paths
+    }
+    #[test]
+    fn rollback_restores_changed_files() {
+        let paths = test_paths("rollback");
+        let changed = paths.home_file("dunstrc");
+        let created = paths.home_file("rofi.rasi");
+        fs::write(&changed, "old").unwrap();
+        let set = BackupSet::new(&paths, "night", 10).unwrap();
+        let executor = Executor::new().with_backup(set);
+        executor.write(changed.as_str(), "new", 0o644).unwrap();
+        executor.write(created.as_str(), "new", 0o644).unwrap();
+        executor.create_dir(paths.file("rendered")).unwrap();
+        executor
+            .write(paths.file("rendered/poly").as_str(), "new", 0o644)
+            .unwrap();
+        let sets = list_backups_in(&paths).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].theme, "night");
+        assert_eq!(sets[0].entries.len(), 2);
+        assert_eq!(rollback_in(&paths, 1).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "old");
+        assert!(fs::metadata(&created).is_err());
+        assert!(list_backups_in(&paths).unwrap().is_empty());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn raven_files_are_not_backed_up() {
+        let paths = test_paths("internal");
+        let mut set = BackupSet::new(&paths, "night", 10).unwrap();
+        set.snapshot(paths.file("rendered/poly")).unwrap();
+        assert!(set.manifest().entries.is_empty());
+        assert!(list_backups_in(&paths).unwrap().is_empty());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn old_sets_are_pruned_unless_backups_are_off() {
+        let paths = test_paths("prune");
+        for _ in 0..3 {
+            let mut set = BackupSet::new(&paths, "night", 10).unwrap();
+            set.snapshot(paths.home_file("dunstrc")).unwrap();
+        }
+        assert_eq!(list_backups_in(&paths).unwrap().len(), 3);
+        assert!(BackupSet::new(&paths, "night", 0).is_none());
+        assert_eq!(list_backups_in(&paths).unwrap().len(), 3);
+        let _set = BackupSet::new(&paths, "night", 2).unwrap();
+        assert_eq!(list_backups_in(&paths).unwrap().len(), 1);
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use compose::find_base;
+use error::*;
+use jsonc::get_key;
+use paths::RavenPaths;
+use serde_json::value::Value;
+use std::{fs, fs::OpenOptions, io::Read, io::Write};
+use themes::{create_theme_in, ThemeStore};
+/// Options and kv keys that capture_theme can read from the live desktop
+pub const CAPTURE_TARGETS: [&str; 14] = [
+    "i3",
+    "poly",
+    "rofi",
+    "dunst",
+    "termite",
+    "openbox",
+    "bspwm",
+    "ranger",
+    "ncmpcpp",
+    "wall",
+    "vscode",
+    "st_tmtheme",
+    "st_scs",
+    "st_subltheme",
+];
+/// Something captured from the desktop, ready to be put into a theme
+enum Captured {
+    /// Contents of an option file
+    File(String),
+    /// An existing file to copy in as an option
+    Copy(String),
+    /// A kv value, with an optional sublime file to copy into the theme's sublt directory
+    Key(String, Option<String>),
+}
+/// Reads a file if it exists
+fn read(path: &str) -> Option<String> {
+    let mut contents = String::new();
+    fs::File::open(path)
+        .and_then(|mut x| x.read_to_string(&mut contents))
+        .ok()
+        .map(|_| contents)
+}
+/// Reads a generated config, removing the base file raven puts in front of it
+fn read_without_base(paths: &RavenPaths, path: &str, base: &str) -> Option<String> {
+    let contents = read(&paths.program(path))?;
+    match find_base(paths, base).and_then(|x| read(&x)) {
+        Some(ref base) if !base.is_empty() && contents.starts_with(base.as_str()) => {
+            Some(contents[base.len()..].to_string())
+        }
+        _ => Some(contents),
+    }
+}
+/// Finds the string value of a key in a settings file like VSCode's settings.json, which may have comments
+fn read_setting(path: &str, key: &str) -> Option<String> {
+    get_key(&read(path)?, &[key])
+        .ok()??
+        .as_str()
+        .map(|x| x.to_string())
+}
+/// Finds the wallpaper set by the last feh run, from ~/.fehbg
+fn feh_wallpaper(paths: &RavenPaths) -> Option<String> {
+    let fehbg = read(&paths.home_file(".fehbg"))?;
+    let line = fehbg.lines().rfind(|x| x.contains("feh "))?;
+    let end = line.rfind('\'')?;
+    let start = line[..end].rfind('\'')?;
+    Some(line[start + 1..end].to_string())
+}
+/// Reads a single target from the live desktop, or None if there's nothing to capture
+fn capture_target(paths: &RavenPaths, target: &str) -> Result<Option<Captured>> {
+    let sublt = paths.program("sublime-text-3/Packages/User");
+    let captured = match target {
+        "i3" => read_without_base(paths, "i3/config", "base_i3").map(Captured::File),
+        "dunst" => read_without_base(paths, "dunst/dunstrc", "base_dunst").map(Captured::File),
+        "openbox" => read_without_base(paths, "openbox/rc.xml", "base_rc.xml").map(Captured::File),
+        "bspwm" => read_without_base(paths, "bspwm/bspwmrc", "base_bspwm").map(Captured::File),
+        "poly" => ["polybar/config", "polybar/config.ini"]
+            .iter()
+            .map(|x| paths.program(x))
+            .find(|x| fs::metadata(x).is_ok())
+            .map(Captured::Copy),
+        "rofi" | "termite" | "ranger" => {
+            let path = match target {
+                "rofi" => paths.program("rofi/theme.rasi"),
+                "termite" => paths.program("termite/config"),
+                _ => paths.program("ranger/rc.conf"),
+            };
+            Some(path)
+                .filter(|x| fs::metadata(x).is_ok())
+                .map(Captured::Copy)
+        }
+        "ncmpcpp" => vec![
+            paths.program("ncmpcpp/config"),
+            paths.home_file(".ncmpcpp/config"),
+        ]
+        .into_iter()
+        .find(|x| fs::metadata(x).is_ok())
+        .map(Captured::Copy),
+        "wall" => feh_wallpaper(paths)
+            .filter(|x| fs::metadata(x).is_ok())
+            .map(Captured::Copy),
+        "vscode" => ["Code/User", "Code - OSS/User"]
+            .iter()
+            .filter_map(|x| {
+                read_setting(
+                    &(paths.program(x) + "/settings.json"),
+                    "workbench.colorTheme",
+                )
+            })
+            .next()
+            .map(|x| Captured::Key(x, None)),
+        "st_tmtheme" | "st_scs" | "st_subltheme" => {
+            let key = if target == "st_subltheme" {
+                "theme"
+            } else {
+                "color_scheme"
+            };
+            // Both scheme keys share color_scheme, so only keep the one matching its extension
+            let matches = |x: &String| match target {
+                "st_tmtheme" => x.ends_with(".tmTheme"),
+                "st_scs" => x.ends_with(".sublime-color-scheme"),
+                _ => true,
+            };
+            read_setting(&(sublt.clone() + "/Preferences.sublime-settings"), key)
+                .filter(matches)
+                .map(|x| {
+                    let file = sublt.clone() + "/" + &x;
+                    if fs::metadata(&file).is_ok() {
+                        Captured::Key(String::from("sublt/") + &x, Some(file))
+                    } else {
+                        Captured::Key(x, None)
+                    }
+                })
+        }
+        _ => return Err(ErrorKind::UnsupportedTarget(target.to_string()).into()),
+    };
+    Ok(captured)
+}
+/// Creates a new theme out of the current desktop's configs for the given targets
+pub fn capture_theme(theme_name: impl Into<String>, targets: &[&str]) -> Result<ThemeStore> {
+    capture_theme_in(&RavenPaths::try_new()?, theme_name, targets)
+}
+/// Creates a new theme in the given paths out of the current desktop's configs for the given targets. Targets with nothing to capture are left out.
+pub fn capture_theme_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    targets: &[&str],
+) -> Result<ThemeStore> {
+    let theme_name = theme_name.into();
+    let mut captured = Vec::new();
+    for target in targets {
+        info!("Capturing {}", target);
+        match capture_target(paths, target)? {
+            Some(c) => captured.push((target.to_string(), c)),
+            None => warn!("Found nothing to capture for {}", target),
+        }
+    }
+    create_theme_in(paths, theme_name.as_str())?;
+    let mut store = ThemeStore::load_in(paths, theme_name.as_str())?;
+    for (target, c) in captured {
+        match c {
+            Captured::File(contents) => {
+                info!("Writing captured {} option", target);
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(paths.theme_file(&theme_name, &target))?
+                    .write_all(contents.as_bytes())?;
+                store.options.push(target);
+            }
+            Captured::Copy(from) => {
+                info!("Copying {} to theme as {}", from, target);
+                fs::copy(&from, paths.theme_file(&theme_name, &target))?;
+                store.options.push(target);
+            }
+            Captured::Key(value, file) => {
+                if let Some(file) = file {
+                    info!("Copying sublime file {} to theme", file);
+                    fs::create_dir_all(paths.theme_file(&theme_name, "sublt"))?;
+                    fs::copy(&file, paths.theme_file(&theme_name, &value))?;
+                }
+                info!("Storing captured key {}", target);
+                store.kv.insert(target, Value::String(value));
+            }
+        }
+    }
+    store.store_in(paths)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{get_config_in, init_in};
+    use std::env;
+    #[test]
+    fn captures_configs_from_the_desktop() {
+        let home = env::temp_dir().join(format!("ravenlib-capture-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        for dir in &["i3", "rofi", "Code/User"] {
+            fs::create_dir_all(paths.program(dir)).unwrap();
+        }
+        fs::write(paths.file("base_i3"), "set $mod Mod4\n").unwrap();
+        fs::write(paths.program("i3/config"), "set $mod Mod4\nbar {}\n").unwrap();
+        fs::write(paths.program("rofi/theme.rasi"), "* {}\n").unwrap();
+        fs::write(
+            paths.program("Code/User/settings.json"),
+            "{\"workbench.colorTheme\": \"Monokai\"}",
+        )
+        .unwrap();
+        fs::write(paths.home_file("wall.png"), "").unwrap();
+        fs::write(
+            paths.home_file(".fehbg"),
+            String::from("#!/bin/sh\nfeh --bg-scale '") + &paths.home_file("wall.png") + "'\n",
+        )
+        .unwrap();
+        let store =
+            capture_theme_in(&paths, "desk", &["i3", "rofi", "vscode", "wall", "dunst"]).unwrap();
+        assert_eq!(store.options, vec!["i3", "rofi", "wall"]);
+        assert_eq!(store.kv["vscode"], Value::String(String::from("Monokai")));
+        assert_eq!(
+            read(&paths.theme_file("desk", "i3")),
+            Some(String::from("bar {}\n"))
+        );
+        assert!(fs::metadata(paths.theme_file("desk", "wall")).is_ok());
+        assert_eq!(get_config_in(&paths).unwrap().editing, "");
+        assert!(capture_theme_in(&paths, "other", &["vim"]).is_err());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use error::*;
+use ini::merge_ini;
+use paths::RavenPaths;
+use std::{collections::BTreeMap, env, fs, io::Read};
+use xml::merge_xml;
+/// How a base file is combined with a theme's option file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    /// The base file goes first, so later settings from the theme win
+    #[default]
+    Prepend,
+    /// The base file goes after the theme's file
+    Append,
+    /// Settings from the theme replace those with the same key in the base file, and the rest are added after it
+    Merge,
+    /// Like merge, but section by section for INI-style configs like dunstrc
+    Ini,
+    /// Top-level elements of the theme replace those of the same name inside the base document's root, for XML configs like openbox's rc.xml
+    Xml,
+}
+/// A base file that an option's config is composed with, stored in config.json
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BaseSpec {
+    /// Name of the file in the raven root. A file of the same name in bases/<hostname> takes precedence.
+    pub file: String,
+    #[serde(default)]
+    pub placement: Placement,
+}
+impl BaseSpec {
+    pub fn new(file: impl Into<String>, placement: Placement) -> BaseSpec {
+        BaseSpec {
+            file: file.into(),
+            placement,
+        }
+    }
+}
+/// Options that have a base file without being configured, which are those whose files are plain text configs. Polybar and Xresources only get base files when they're configured in Config.bases.
+pub const DEFAULT_BASE_OPTIONS: [&str; 9] = [
+    "wm", "i3", "bspwm", "dunst", "openbox", "rofi", "termite", "ranger", "ncmpcpp",
+];
+/// Base files of an option that isn't configured, which is base_<option> put in front of it. i3 and openbox keep the names they've always used, dunst and termite configs are merged as INI and openbox's as XML. Other options, like scripts and sublime files, have none.
+pub fn default_bases(option: &str) -> Vec<BaseSpec> {
+    if !DEFAULT_BASE_OPTIONS.contains(&option) {
+        return Vec::new();
+    }
+    let file = match option {
+        "wm" | "i3" => String::from("base_i3"),
+        "openbox" => String::from("base_rc.xml"),
+        _ => String::from("base_") + option,
+    };
+    let placement = match option {
+        "dunst" | "termite" => Placement::Ini,
+        "openbox" => Placement::Xml,
+        _ => Placement::Prepend,
+    };
+    vec![BaseSpec::new(file, placement)]
+}
+/// Base files of an option in the order they're applied, from the configured ones if there are any
+pub fn bases_for(bases: &BTreeMap<String, Vec<BaseSpec>>, option: &str) -> Vec<BaseSpec> {
+    bases
+        .get(option)
+        .cloned()
+        .unwrap_or_else(|| default_bases(option))
+}
+/// The machine's hostname, from the kernel or /etc/hostname, falling back to $HOSTNAME
+pub fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|file| {
+            let mut name = String::new();
+            fs::File::open(file)
+                .and_then(|mut x| x.read_to_string(&mut name))
+                .ok()
+                .map(|_| name)
+        })
+        .chain(env::var("HOSTNAME").ok())
+        .map(|x| x.trim().to_string())
+        .find(|x| !x.is_empty())
+}
+/// Finds a base file, preferring this machine's version of it
+pub fn find_base(paths: &RavenPaths, file: &str) -> Option<String> {
+    find_base_for(paths, file, hostname().as_deref())
+}
+/// Finds a base file, preferring the version in bases/<host> if a host is given
+pub fn find_base_for(paths: &RavenPaths, file: &str, host: Option<&str>) -> Option<String> {
+    host.map(|host| paths.file("bases") + "/" + host + "/" + file)
+        .into_iter()
+        .chain(Some(paths.file(file)))
+        .find(|x| fs::metadata(x).is_ok())
+}
+/// Joins two pieces of a config, making sure the first ends its last line
+fn join(first: &str, second: &str) -> String {
+    if first.is_empty() || first.ends_with('\n') {
+        first.to_string() + second
+    } else {
+        first.to_string() + "\n" + second
+    }
+}
+/// The key of a setting line like `key = value` or `key: value`, or None for comments and other lines
+pub fn setting_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('!') {
+        return None;
+    }
+    let key = line[..line.find(['=', ':'])?].trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(key)
+    }
+}
+/// Replaces settings of the base with those of the same key in contents, adding the rest of contents after it
+fn merge_settings(base: &str, contents: &str) -> String {
+    let mut theme: BTreeMap<&str, &str> = BTreeMap::new();
+    for line in contents.lines() {
+        if let Some(key) = setting_key(line) {
+            theme.insert(key, line);
+        }
+    }
+    let mut merged = String::new();
+    let mut used = Vec::new();
+    for line in base.lines() {
+        match setting_key(line).and_then(|x| theme.get(x).map(|l| (x, l))) {
+            Some((key, theme_line)) => {
+                merged = merged + theme_line + "\n";
+                used.push(key);
+            }
+            None => merged = merged + line + "\n",
+        }
+    }
+    for line in contents.lines() {
+        if setting_key(line)
+            .map(|x| !used.contains(&x))
+            .unwrap_or(true)
+        {
+            merged = merged + line + "\n";
+        }
+    }
+    merged
+}
+/// Combines a base file with an option's contents
+pub fn compose(base: &str, contents: &str, placement: Placement) -> Result<String> {
+    Ok(match placement {
+        Placement::Prepend => join(base, contents),
+        Placement::Append => join(contents, base),
+        Placement::Merge => merge_settings(base, contents),
+        Placement::Ini => merge_ini(base, contents),
+        Placement::Xml => merge_xml(base, contents)?,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn composes_with_each_placement() {
+        let base = "font = mono\nsize = 10";
+        let theme = "size = 12\ncolor = red\n";
+        assert_eq!(
+            compose(base, theme, Placement::Prepend).unwrap(),
+            "font = mono\nsize = 10\nsize = 12\ncolor = red\n"
+        );
+        assert_eq!(
+            compose(base, theme, Placement::Append).unwrap(),
+            "size = 12\ncolor = red\nfont = mono\nsize = 10"
+        );
+        assert_eq!(
+            compose(base, theme, Placement::Merge).unwrap(),
+            "font = mono\nsize = 12\ncolor = red\n"
+        );
+    }
+    #[test]
+    fn only_configured_options_get_polybar_and_xresources_bases() {
+        let mut bases = BTreeMap::new();
+        assert_eq!(
+            bases_for(&bases, "wm"),
+            vec![BaseSpec::new("base_i3", Placement::Prepend)]
+        );
+        assert_eq!(
+            bases_for(&bases, "dunst"),
+            vec![BaseSpec::new("base_dunst", Placement::Ini)]
+        );
+        assert!(bases_for(&bases, "poly").is_empty());
+        assert!(bases_for(&bases, "xres").is_empty());
+        bases.insert(
+            String::from("poly"),
+            vec![BaseSpec::new("base_poly", Placement::Append)],
+        );
+        assert_eq!(
+            bases_for(&bases, "poly"),
+            vec![BaseSpec::new("base_poly", Placement::Append)]
+        );
+    }
+    #[test]
+    fn prefers_the_hosts_base_file() {
+        let home = env::temp_dir().join(format!("ravenlib-compose-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        fs::create_dir_all(paths.file("bases/laptop")).unwrap();
+        fs::write(paths.file("base_i3"), "").unwrap();
+        fs::write(paths.file("bases/laptop/base_i3"), "").unwrap();
+        assert_eq!(
+            find_base_for(&paths, "base_i3", Some("laptop")),
+            Some(paths.file("bases/laptop/base_i3"))
+        );
+        assert_eq!(
+            find_base_for(&paths, "base_i3", Some("desktop")),
+            Some(paths.file("base_i3"))
+        );
+        assert_eq!(find_base_for(&paths, "base_rofi", None), None);
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+use palette::{Color, Palette};
+use themes::Theme;
+/// Minimum WCAG contrast ratio for normal text, required between foreground and background
+pub const TEXT_CONTRAST: f64 = 4.5;
+/// Minimum WCAG contrast ratio for large text, required between terminal colors and the background
+pub const COLOR_CONTRAST: f64 = 3.0;
+/// A foreground color checked against the background it's drawn on
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastCheck {
+    /// Where the colors come from, like palette or an option name
+    pub source: String,
+    /// Name of the foreground color, like fg or color4
+    pub foreground: String,
+    pub fg: Color,
+    pub bg: Color,
+    /// WCAG contrast ratio, from 1 to 21
+    pub ratio: f64,
+    /// Ratio needed for the pair to count as readable
+    pub minimum: f64,
+}
+impl ContrastCheck {
+    /// Whether the pair is readable
+    pub fn passes(&self) -> bool {
+        self.ratio >= self.minimum
+    }
+}
+/// WCAG relative luminance of a color
+pub fn luminance(color: &Color) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
+/// WCAG contrast ratio between two colors
+pub fn contrast_ratio(a: &Color, b: &Color) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+/// Checks fg and color1 through color15 against bg. color8 is left out, since it's meant to be dim.
+pub fn check_colors(source: &str, colors: &Palette) -> Vec<ContrastCheck> {
+    let bg = match colors.get("bg") {
+        Some(bg) => *bg,
+        None => return Vec::new(),
+    };
+    let mut names = vec![(String::from("fg"), TEXT_CONTRAST)];
+    names.extend(
+        (1..16)
+            .filter(|x| *x != 8)
+            .map(|x| (format!("color{}", x), COLOR_CONTRAST)),
+    );
+    names
+        .into_iter()
+        .filter_map(|(name, minimum)| {
+            colors.get(&name).map(|fg| ContrastCheck {
+                source: source.to_string(),
+                ratio: contrast_ratio(fg, &bg),
+                foreground: name,
+                fg: *fg,
+                bg,
+                minimum,
+            })
+        })
+        .collect()
+}
+/// Maps resource names like foreground or color4 to the palette names used by checks
+fn color_name(name: &str) -> Option<String> {
+    match name {
+        "background" => Some(String::from("bg")),
+        "foreground" => Some(String::from("fg")),
+        _ if name.starts_with("color") && name[5..].parse::<u8>().is_ok() => Some(name.to_string()),
+        _ => None,
+    }
+}
+/// Reads colors out of an Xresources file
+pub fn parse_xres(contents: &str) -> Palette {
+    let mut colors = Palette::new();
+    for line in contents.lines().map(|x| x.trim()) {
+        if line.starts_with('!') || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, ':');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value),
+            _ => continue,
+        };
+        let name = key.rsplit(['.', '*']).next().unwrap_or(key);
+        if let (Some(name), Some(color)) = (color_name(name), Color::parse(value)) {
+            colors.insert(name, color);
+        }
+    }
+    colors
+}
+/// Reads colors out of the [colors] section of a termite config
+pub fn parse_termite(contents: &str) -> Palette {
+    let mut colors = Palette::new();
+    let mut in_colors = false;
+    for line in contents.lines().map(|x| x.trim()) {
+        if line.starts_with('[') {
+            in_colors = line == "[colors]";
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        if let (true, Some(key), Some(value)) = (in_colors, parts.next(), parts.next()) {
+            if let (Some(name), Some(color)) = (color_name(key.trim()), Color::parse(value)) {
+                colors.insert(name, color);
+            }
+        }
+    }
+    colors
+}
+/// Checks the text-color of each rofi block against its background-color, falling back to the colors set for every element with *
+pub fn check_rasi(source: &str, contents: &str) -> Vec<ContrastCheck> {
+    let mut blocks = Vec::new();
+    for block in contents.split('}') {
+        let mut parts = block.splitn(2, '{');
+        let (selector, body) = match (parts.next(), parts.next()) {
+            (Some(selector), Some(body)) => (selector.trim(), body),
+            _ => continue,
+        };
+        let mut colors = Palette::new();
+        for prop in body.split(';') {
+            let mut parts = prop.splitn(2, ':');
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                let name = match key.trim() {
+                    "background-color" | "background" => "bg",
+                    "text-color" | "foreground" => "fg",
+                    _ => continue,
+                };
+                if let Some(color) = Color::parse(value) {
+                    colors.insert(name.to_string(), color);
+                }
+            }
+        }
+        blocks.push((selector.to_string(), colors));
+    }
+    let global = blocks
+        .iter()
+        .find(|x| x.0 == "*")
+        .map(|x| x.1.clone())
+        .unwrap_or_default();
+    let mut checks = Vec::new();
+    for (selector, mut colors) in blocks {
+        if selector != "*" && !colors.contains_key("fg") && !colors.contains_key("bg") {
+            continue;
+        }
+        for (k, v) in &global {
+            colors.entry(k.clone()).or_insert(*v);
+        }
+        if let (Some(fg), Some(bg)) = (colors.get("fg"), colors.get("bg")) {
+            checks.push(ContrastCheck {
+                source: source.to_string(),
+                foreground: selector,
+                fg: *fg,
+                bg: *bg,
+                ratio: contrast_ratio(fg, bg),
+                minimum: TEXT_CONTRAST,
+            });
+        }
+    }
+    checks
+}
+/// How the colors in an option's file are checked, for options whose files carry colors that can be
+fn color_checker(option: &str) -> Option<fn(&str, &str) -> Vec<ContrastCheck>> {
+    let checker: fn(&str, &str) -> Vec<ContrastCheck> = match option {
+        "xres" | "xres_m" => |source, contents| check_colors(source, &parse_xres(contents)),
+        "termite" => |source, contents| check_colors(source, &parse_termite(contents)),
+        "rofi" => check_rasi,
+        _ => return None,
+    };
+    Some(checker)
+}
+/// Whether an option's files carry colors that can be checked
+pub fn has_colors(option: &str) -> bool {
+    color_checker(option).is_some()
+}
+/// Checks the colors in an option file. Options without checkable colors give no results.
+pub fn check_option(option: &str, contents: &str) -> Vec<ContrastCheck> {
+    match color_checker(option) {
+        Some(check) => check(option, contents),
+        None => Vec::new(),
+    }
+}
+/// Checks the contrast of a loaded theme's palette and of the colors in its option files
+pub fn check_theme_contrast(theme: &Theme) -> Vec<ContrastCheck> {
+    let mut checks = check_colors("palette", &theme.palette);
+    for option in &theme.option_names {
+        if let Some(check) = color_checker(option) {
+            if let Ok(contents) = theme.read_option(option) {
+                checks.extend(check(option, &contents));
+            }
+        }
+    }
+    checks
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::init_in;
+    use paths::RavenPaths;
+    use std::{env, fs};
+    use themes::{load_theme_in, new_theme_in, ThemeStore};
+    #[test]
+    fn measures_contrast() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(0xff, 0xff, 0xff);
+        assert!((contrast_ratio(&black, &white) - 21.0).abs() < 1e-9);
+        assert!((contrast_ratio(&white, &white) - 1.0).abs() < 1e-9);
+    }
+    #[test]
+    fn checks_option_files() {
+        let xres =
+            "! comment\n*.background: #000000\n*.foreground: #111111\nURxvt*color4: #ffffff\n";
+        let checks = check_option("xres", xres);
+        assert_eq!(checks.len(), 2);
+        assert!(!checks
+            .iter()
+            .find(|x| x.foreground == "fg")
+            .unwrap()
+            .passes());
+        assert!(checks
+            .iter()
+            .find(|x| x.foreground == "color4")
+            .unwrap()
+            .passes());
+        let rasi = "* { background-color: #000000; text-color: #ffffff; }\nentry { text-color: #050505; }\n";
+        let checks = check_option("rofi", rasi);
+        assert_eq!(checks.len(), 2);
+        assert!(!checks
+            .iter()
+            .find(|x| x.foreground == "entry")
+            .unwrap()
+            .passes());
+        assert!(check_option("wm", xres).is_empty());
+        assert!(has_colors("termite") && !has_colors("wm"));
+    }
+    #[test]
+    fn checks_themes() {
+        let home = env::temp_dir().join(format!("ravenlib-contrast-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(
+            paths.theme_file("night", "termite"),
+            "[colors]\nbackground = #000000\nforeground = {{fg}}\n",
+        )
+        .unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = vec![String::from("termite")];
+        store
+            .palette
+            .insert(String::from("bg"), Color::new(0xff, 0xff, 0xff));
+        store
+            .palette
+            .insert(String::from("fg"), Color::new(0x10, 0x10, 0x10));
+        store.store_in(&paths).unwrap();
+        let theme = load_theme_in(&paths, "night").unwrap();
+        let checks = check_theme_contrast(&theme);
+        assert_eq!(checks.len(), 2);
+        assert!(checks
+            .iter()
+            .find(|x| x.source == "palette")
+            .unwrap()
+            .passes());
+        // The termite file's foreground is rendered from the palette, but its background isn't
+        assert!(!checks
+            .iter()
+            .find(|x| x.source == "termite")
+            .unwrap()
+            .passes());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use loaders::LoaderRegistry;
+use paths::RavenPaths;
+use requirements::{is_met, Requirement};
+use std::{env, fs, io::Read};
+use themes::ROption;
+/// What was found of the program behind a single option
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detected {
+    /// Option or kv key name
+    pub option: String,
+    /// Requirements of the option's loader that aren't met
+    pub missing: Vec<Requirement>,
+    pub running: bool,
+}
+impl Detected {
+    /// Whether everything the option needs was found
+    pub fn installed(&self) -> bool {
+        self.missing.is_empty()
+    }
+    /// The ROption this is for, if it isn't a kv key
+    pub fn roption(&self) -> Option<ROption> {
+        serde_json::from_value(json!(self.option)).ok()
+    }
+}
+/// Programs found on the system, for each option raven knows about
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Environment {
+    pub programs: Vec<Detected>,
+}
+impl Environment {
+    /// Gets what was found for an option
+    pub fn get(&self, option: &str) -> Option<&Detected> {
+        self.programs.iter().find(|x| x.option == option)
+    }
+    /// Whether the program behind an option is installed. Options environment doesn't know about count as installed.
+    pub fn installed(&self, option: &str) -> bool {
+        self.get(option).map(|x| x.installed()).unwrap_or(true)
+    }
+    /// Whether the program behind an option is running
+    pub fn running(&self, option: &str) -> bool {
+        self.get(option).map(|x| x.running).unwrap_or(false)
+    }
+    /// Why an option can't be loaded, if its program is missing
+    pub fn missing_reason(&self, option: &str) -> Option<String> {
+        self.get(option).filter(|x| !x.installed()).map(|x| {
+            format!(
+                "option {} needs {}, which can't be found",
+                option,
+                x.missing
+                    .iter()
+                    .map(|x| x.describe())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        })
+    }
+    /// Options whose programs are installed, which a new theme could use
+    pub fn suggested_options(&self) -> Vec<String> {
+        self.programs
+            .iter()
+            .filter(|x| x.installed())
+            .map(|x| x.option.clone())
+            .collect()
+    }
+}
+/// Finds a binary in the given $PATH-style list of directories
+pub fn find_binary(path_var: &str, binary: &str) -> Option<String> {
+    env::split_paths(path_var)
+        .map(|x| x.join(binary))
+        .find(|x| x.is_file())
+        .and_then(|x| x.to_str().map(|x| x.to_string()))
+}
+/// Names of all processes listed in the given proc directory
+pub fn running_processes(proc_dir: &str) -> Vec<String> {
+    let entries = match fs::read_dir(proc_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|x| x.ok())
+        .filter(|x| x.file_name().to_string_lossy().parse::<u32>().is_ok())
+        .filter_map(|x| {
+            let mut comm = String::new();
+            fs::File::open(x.path().join("comm"))
+                .and_then(|mut f| f.read_to_string(&mut comm))
+                .ok()
+                .map(|_| comm.trim().to_string())
+        })
+        .collect()
+}
+/// Detects installed and running programs for ravenlib's own loaders, using $PATH and /proc
+pub fn detect() -> Environment {
+    detect_in(&RavenPaths::new(), &LoaderRegistry::default())
+}
+/// Detects whether the requirements of the given loaders are met under the given paths, and which of their programs are running
+pub fn detect_in(paths: &RavenPaths, loaders: &LoaderRegistry) -> Environment {
+    detect_with(
+        paths,
+        loaders,
+        &env::var("PATH").unwrap_or_default(),
+        &running_processes("/proc"),
+    )
+}
+/// Detects whether the requirements of the given loaders are met, looking for programs in the given $PATH-style list, and which are running among the given process names
+pub fn detect_with(
+    paths: &RavenPaths,
+    loaders: &LoaderRegistry,
+    path_var: &str,
+    processes: &[String],
+) -> Environment {
+    info!("Detecting installed programs");
+    let programs = loaders
+        .names()
+        .into_iter()
+        .filter_map(|name| loaders.get(&name).map(|x| (name, x)))
+        .map(|(option, loader)| Detected {
+            missing: loader
+                .requirements(paths)
+                .into_iter()
+                .filter(|x| !is_met(x, path_var))
+                .collect(),
+            running: loader
+                .processes()
+                .iter()
+                .any(|x| processes.iter().any(|p| p == x)),
+            option,
+        })
+        .collect();
+    Environment { programs }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn detects_installed_and_running_programs() {
+        let home = env::temp_dir().join(format!("ravenlib-environment-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        let bin = paths.home_file("bin");
+        fs::create_dir_all(&bin).unwrap();
+        for program in &["i3-msg", "polybar"] {
+            fs::write(bin.clone() + "/" + program, "").unwrap();
+        }
+        let env = detect_with(
+            &paths,
+            &LoaderRegistry::default(),
+            &bin,
+            &[String::from("polybar")],
+        );
+        assert!(env.installed("wm") && env.installed("poly"));
+        assert!(!env.installed("dunst"));
+        assert!(env.missing_reason("dunst").unwrap().contains("dunst"));
+        assert_eq!(env.missing_reason("wm"), None);
+        assert!(env.running("poly") && !env.running("wm"));
+        assert!(env.installed("not an option"));
+        let suggested = env.suggested_options();
+        assert!(
+            suggested.contains(&String::from("wm")) && !suggested.contains(&String::from("dunst"))
+        );
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn reads_running_process_names() {
+        let proc_dir = env::temp_dir().join(format!("ravenlib-proc-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&proc_dir);
+        for (dir, comm) in &[("42", "polybar\n"), ("self", "raven\n")] {
+            fs::create_dir_all(proc_dir.join(dir)).unwrap();
+            fs::write(proc_dir.join(dir).join("comm"), comm).unwrap();
+        }
+        assert_eq!(
+            running_processes(&proc_dir.to_string_lossy()),
+            vec![String::from("polybar")]
+        );
+        fs::remove_dir_all(&proc_dir).unwrap();
+    }
+}
@@ -7,6 +7,7 @@ error_chain! {
     }
     foreign_links {
         Fmt(::std::fmt::Error);
+        Image(::image::ImageError);
         Io(::std::io::Error);
         Network(::reqwest::Error);
         Parse(::serde_json::error::Error);
@@ -20,6 +21,46 @@ error_chain! {
             description("invalid theme name")
             display("invalid theme name: '{}'", t)
         }
+        ThemeCycle(t: String) {
+            description("theme extends itself")
+            display("theme extends itself: {}", t)
+        }
+        TemplateError(t: String) {
+            description("invalid template in option file")
+            display("invalid template: {}", t)
+        }
+        IncompletePalette(t: String) {
+            description("palette is missing colors")
+            display("palette is missing colors: {}", t)
+        }
+        UnsupportedTarget(t: String) {
+            description("option can't be generated from a palette")
+            display("option '{}' can't be generated from a palette", t)
+        }
+        InvalidJsonc(t: String) {
+            description("settings file can't be parsed")
+            display("settings file can't be parsed: {}", t)
+        }
+        InvalidXml(t: String) {
+            description("config can't be parsed as XML")
+            display("config can't be parsed as XML: {}", t)
+        }
+        BrokenBlock(t: String) {
+            description("raven block in config isn't closed")
+            display("raven block in config is missing its end marker '{}'", t)
+        }
+        InvalidExtractOptions(t: String) {
+            description("invalid palette extraction options")
+            display("invalid palette extraction options: {}", t)
+        }
+        NoHome {
+            description("home directory can't be found")
+            display("home directory can't be found, set $RAVEN_HOME to choose raven's directory")
+        }
+        InvalidKeyValue(k: String) {
+            description("key-value option is not a string")
+            display("value of key '{}' is not a string", k)
+        }
     }
 }
 error_chain! {
@@ -0,0 +1,213 @@
+use error::*;
+use image;
+use palette::{Color, Palette};
+/// Tunables for building a palette out of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractOptions {
+    /// Builds a light palette, with a light background and dark foreground, instead of a dark one
+    pub light: bool,
+    /// Multiplier for the saturation of accent colors. 1.0 keeps the image's own saturation.
+    pub saturation: f64,
+    /// Number of k-means iterations to run
+    pub iterations: usize,
+    /// Size the image is scaled down to before its pixels are clustered
+    pub sample_size: u32,
+}
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            light: false,
+            saturation: 1.0,
+            iterations: 10,
+            sample_size: 128,
+        }
+    }
+}
+/// Squared distance between two colors
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+/// Groups colors into k clusters with k-means, returning the center of each non-empty cluster. Starting centers are spread evenly by brightness, so results are deterministic.
+pub fn cluster_colors(colors: &[Color], k: usize, iterations: usize) -> Vec<Color> {
+    if colors.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let mut points: Vec<[f64; 3]> = colors
+        .iter()
+        .map(|x| [x.r as f64, x.g as f64, x.b as f64])
+        .collect();
+    points.sort_by(|a, b| {
+        (a[0] + a[1] + a[2])
+            .partial_cmp(&(b[0] + b[1] + b[2]))
+            .unwrap()
+    });
+    let mut centers: Vec<[f64; 3]> = (0..k)
+        .map(|i| points[i * (points.len() - 1) / (k - 1).max(1)])
+        .collect();
+    let mut counts = vec![0; k];
+    for _ in 0..iterations.max(1) {
+        let mut sums = vec![[0.0; 3]; k];
+        counts = vec![0; k];
+        for point in &points {
+            let nearest = (0..k)
+                .min_by(|a, b| {
+                    distance(point, &centers[*a])
+                        .partial_cmp(&distance(point, &centers[*b]))
+                        .unwrap()
+                })
+                .unwrap();
+            for c in 0..3 {
+                sums[nearest][c] += point[c];
+            }
+            counts[nearest] += 1;
+        }
+        for i in 0..k {
+            if counts[i] > 0 {
+                for c in 0..3 {
+                    centers[i][c] = sums[i][c] / counts[i] as f64;
+                }
+            }
+        }
+    }
+    centers
+        .iter()
+        .zip(counts)
+        .filter(|x| x.1 > 0)
+        .map(|(x, _)| Color::new(x[0].round() as u8, x[1].round() as u8, x[2].round() as u8))
+        .collect()
+}
+/// Moves a color's lightness into the given range and scales its saturation
+fn adjust(color: &Color, saturation: f64, min_l: f64, max_l: f64) -> Color {
+    let (h, s, l) = color.to_hsl();
+    Color::from_hsl(h, s * saturation, l.max(min_l).min(max_l))
+}
+/// Builds a 16-color terminal palette, plus bg, fg and cursor, out of clustered colors
+pub fn build_palette(clusters: &[Color], options: &ExtractOptions) -> Palette {
+    let mut palette = Palette::new();
+    if clusters.is_empty() {
+        return palette;
+    }
+    let mut clusters = clusters.to_vec();
+    clusters.sort_by(|a, b| a.to_hsl().2.partial_cmp(&b.to_hsl().2).unwrap());
+    if options.light {
+        clusters.reverse();
+    }
+    let (bg, fg) = if options.light {
+        (
+            adjust(&clusters[0], 0.3, 0.9, 1.0),
+            adjust(&clusters[clusters.len() - 1], 0.3, 0.0, 0.2),
+        )
+    } else {
+        (
+            adjust(&clusters[0], 0.5, 0.0, 0.1),
+            adjust(&clusters[clusters.len() - 1], 0.3, 0.8, 1.0),
+        )
+    };
+    let (min_l, max_l, shift) = if options.light {
+        (0.3, 0.5, -0.1)
+    } else {
+        (0.45, 0.7, 0.1)
+    };
+    let mut accents: Vec<Color> = (0..6)
+        .map(|i| clusters[(i + 1) % clusters.len()])
+        .map(|x| adjust(&x, options.saturation, min_l, max_l))
+        .collect();
+    accents.sort_by(|a, b| a.to_hsl().0.partial_cmp(&b.to_hsl().0).unwrap());
+    palette.insert(String::from("color0"), bg);
+    palette.insert(String::from("color7"), fg);
+    palette.insert(String::from("color8"), bg.lighten(shift * 2.5));
+    palette.insert(String::from("color15"), fg);
+    for (i, accent) in accents.iter().enumerate() {
+        palette.insert(format!("color{}", i + 1), *accent);
+        palette.insert(format!("color{}", i + 9), accent.lighten(shift));
+    }
+    palette.insert(String::from("bg"), bg);
+    palette.insert(String::from("fg"), fg);
+    palette.insert(String::from("cursor"), fg);
+    palette
+}
+/// Generates a palette from an image file without running any external programs. A sample size of 0 is an error.
+pub fn extract_palette(path: impl Into<String>, options: &ExtractOptions) -> Result<Palette> {
+    let path = path.into();
+    if options.sample_size == 0 {
+        return Err(
+            ErrorKind::InvalidExtractOptions(String::from("sample size must be above 0")).into(),
+        );
+    }
+    info!("Opening image {}", path);
+    let img = image::open(&path)?
+        .thumbnail(options.sample_size, options.sample_size)
+        .to_rgb();
+    let colors: Vec<Color> = img
+        .pixels()
+        .map(|x| Color::new(x.data[0], x.data[1], x.data[2]))
+        .collect();
+    info!("Clustering {} sampled pixels", colors.len());
+    let clusters = cluster_colors(&colors, 8, options.iterations);
+    Ok(build_palette(&clusters, options))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    #[test]
+    fn clusters_colors() {
+        let mut colors = vec![Color::new(0xf0, 0x10, 0x10); 5];
+        colors.extend(vec![Color::new(0x10, 0x10, 0xf0); 3]);
+        colors.push(Color::new(0xe0, 0x20, 0x20));
+        let clusters = cluster_colors(&colors, 2, 10);
+        assert_eq!(clusters.len(), 2);
+        assert!(clusters.contains(&Color::new(0x10, 0x10, 0xf0)));
+        assert!(cluster_colors(&[], 8, 10).is_empty());
+    }
+    #[test]
+    fn builds_dark_and_light_palettes() {
+        let clusters = vec![
+            Color::new(0x20, 0x20, 0x30),
+            Color::new(0xc0, 0x40, 0x40),
+            Color::new(0x40, 0xa0, 0x40),
+            Color::new(0xe0, 0xe0, 0xd0),
+        ];
+        let dark = build_palette(&clusters, &ExtractOptions::default());
+        for i in 0..16 {
+            assert!(dark.contains_key(&format!("color{}", i)));
+        }
+        assert!(dark["bg"].to_hsl().2 <= 0.1 + 1e-9);
+        assert!(dark["fg"].to_hsl().2 >= 0.8 - 1e-9);
+        let light = build_palette(
+            &clusters,
+            &ExtractOptions {
+                light: true,
+                ..ExtractOptions::default()
+            },
+        );
+        assert!(light["bg"].to_hsl().2 >= 0.9 - 1e-9);
+        assert!(light["fg"].to_hsl().2 <= 0.2 + 1e-9);
+    }
+    #[test]
+    fn extracts_palettes_from_images() {
+        let path = env::temp_dir().join(format!("ravenlib-extract-{}.png", ::std::process::id()));
+        image::ImageBuffer::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                image::Rgb {
+                    data: [0x10, 0x10, 0x20],
+                }
+            } else {
+                image::Rgb {
+                    data: [0xe0, 0x80, 0x40],
+                }
+            }
+        })
+        .save(&path)
+        .unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let palette = extract_palette(path.as_str(), &ExtractOptions::default()).unwrap();
+        assert!(palette.contains_key("bg") && palette.contains_key("color15"));
+        let options = ExtractOptions {
+            sample_size: 0,
+            ..ExtractOptions::default()
+        };
+        assert!(extract_palette(path.as_str(), &options).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use config::get_config_in;
+use error::*;
+use palette::{Color, Palette};
+use paths::RavenPaths;
+use std::{fs::OpenOptions, io::Write};
+use themes::{create_theme_in, ThemeStore};
+/// Options that generate_theme can write files for
+pub const TARGETS: [&str; 6] = ["xres", "rofi", "dunst", "termite", "i3", "poly"];
+/// Fills in the colors generated files use. bg and fg (or color0 and color7) are required, everything else is derived from them.
+pub fn complete_palette(palette: &Palette) -> Result<Palette> {
+    let mut full = palette.clone();
+    let get = |names: &[&str]| names.iter().filter_map(|x| palette.get(*x)).next().cloned();
+    let bg = get(&["bg", "color0"])
+        .ok_or_else(|| Error::from(ErrorKind::IncompletePalette(String::from("bg"))))?;
+    let fg = get(&["fg", "color7"])
+        .ok_or_else(|| Error::from(ErrorKind::IncompletePalette(String::from("fg"))))?;
+    let accent = get(&["accent", "color4"]).unwrap_or(Color::from_hsl(210.0, 0.6, 0.55));
+    let (_, s, l) = accent.to_hsl();
+    // Hues of red, green, yellow, blue, magenta and cyan, in terminal order
+    let hues = [0.0, 120.0, 60.0, 240.0, 300.0, 180.0];
+    full.entry(String::from("bg")).or_insert(bg);
+    full.entry(String::from("fg")).or_insert(fg);
+    full.entry(String::from("accent")).or_insert(accent);
+    full.entry(String::from("cursor")).or_insert(fg);
+    full.entry(String::from("color0")).or_insert(bg);
+    full.entry(String::from("color7")).or_insert(fg);
+    for (i, hue) in hues.iter().enumerate() {
+        full.entry(format!("color{}", i + 1))
+            .or_insert(Color::from_hsl(*hue, s, l));
+    }
+    for i in 0..8 {
+        let normal = full[&format!("color{}", i)];
+        full.entry(format!("color{}", i + 8))
+            .or_insert(normal.lighten(0.1));
+    }
+    Ok(full)
+}
+/// The contents of an option file for the given target, with placeholders for palette colors. Polybar gets a bar for each of the given bar names.
+pub fn option_template(target: &str, bars: &[String]) -> Result<String> {
+    let mut out = String::new();
+    match target {
+        "xres" => {
+            out.push_str("*.background: {{bg}}\n*.foreground: {{fg}}\n*.cursorColor: {{cursor}}\n");
+            for i in 0..16 {
+                out = out + &format!("*.color{}: {{{{color{}}}}}\n", i, i);
+            }
+        }
+        "termite" => {
+            out.push_str("[colors]\nbackground = {{bg}}\nforeground = {{fg}}\ncursor = {{cursor}}\n");
+            for i in 0..16 {
+                out = out + &format!("color{} = {{{{color{}}}}}\n", i, i);
+            }
+        }
+        "rofi" => out.push_str(
+            "* {\n    background-color: {{bg}};\n    text-color: {{fg}};\n    border-color: {{accent}};\n}\n\
+             window {\n    border: 2px;\n    padding: 10px;\n}\n\
+             prompt {\n    text-color: {{accent}};\n}\n\
+             element selected {\n    background-color: {{accent}};\n    text-color: {{bg}};\n}\n",
+        ),
+        "dunst" => out.push_str(
+            "[global]\n    frame_color = \"{{accent}}\"\n    separator_color = frame\n\n\
+             [urgency_low]\n    background = \"{{bg}}\"\n    foreground = \"{{color8}}\"\n\n\
+             [urgency_normal]\n    background = \"{{bg}}\"\n    foreground = \"{{fg}}\"\n\n\
+             [urgency_critical]\n    background = \"{{bg}}\"\n    foreground = \"{{fg}}\"\n    frame_color = \"{{color1}}\"\n",
+        ),
+        "i3" => out.push_str(
+            "# class border background text indicator child_border\n\
+             client.focused {{accent}} {{accent}} {{bg}} {{color12}} {{accent}}\n\
+             client.focused_inactive {{color8}} {{color8}} {{fg}} {{color8}} {{color8}}\n\
+             client.unfocused {{bg}} {{bg}} {{color8}} {{bg}} {{bg}}\n\
+             client.urgent {{color1}} {{color1}} {{bg}} {{color1}} {{color1}}\n\
+             client.background {{bg}}\n",
+        ),
+        "poly" => {
+            out.push_str(
+                "[colors]\nbackground = {{bg}}\nforeground = {{fg}}\naccent = {{accent}}\nurgent = {{color1}}\n",
+            );
+            for bar in bars {
+                out = out
+                    + "\n[bar/"
+                    + bar
+                    + "]\nbackground = ${colors.background}\nforeground = ${colors.foreground}\n\
+                       height = 24\npadding-right = 2\nmodules-right = date\n";
+            }
+            out.push_str(
+                "\n[module/date]\ntype = internal/date\ndate = %Y-%m-%d %H:%M\nformat-underline = ${colors.accent}\n",
+            );
+        }
+        _ => return Err(ErrorKind::UnsupportedTarget(target.to_string()).into()),
+    }
+    Ok(out)
+}
+/// Creates a new theme whose option files for the given targets are colored by the palette
+pub fn generate_theme(
+    theme_name: impl Into<String>,
+    palette: &Palette,
+    targets: &[&str],
+) -> Result<ThemeStore> {
+    generate_theme_in(&RavenPaths::try_new()?, theme_name, palette, targets)
+}
+/// Creates a new theme in the given paths whose option files for the given targets are colored by the palette
+pub fn generate_theme_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    palette: &Palette,
+    targets: &[&str],
+) -> Result<ThemeStore> {
+    let theme_name = theme_name.into();
+    let palette = complete_palette(palette)?;
+    let bars = get_config_in(paths)?.polybar;
+    let files = targets
+        .iter()
+        .map(|x| Ok((x.to_string(), option_template(x, &bars)?)))
+        .collect::<Result<Vec<(String, String)>>>()?;
+    create_theme_in(paths, theme_name.as_str())?;
+    for (target, contents) in files {
+        info!("Writing generated {} option", target);
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(paths.theme_file(&theme_name, &target))?
+            .write_all(contents.as_bytes())?;
+    }
+    let mut store = ThemeStore::load_in(paths, theme_name.as_str())?;
+    store.options = targets.iter().map(|x| x.to_string()).collect();
+    store.palette = palette;
+    info!("Storing generated theme {}", theme_name);
+    store.store_in(paths)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::init_in;
+    use std::{env, fs};
+    use template::render;
+    fn palette() -> Palette {
+        let mut palette = Palette::new();
+        palette.insert(String::from("bg"), Color::new(0x10, 0x10, 0x10));
+        palette.insert(String::from("fg"), Color::new(0xe0, 0xe0, 0xe0));
+        palette
+    }
+    #[test]
+    fn completes_palettes_from_bg_and_fg() {
+        let full = complete_palette(&palette()).unwrap();
+        for i in 0..16 {
+            assert!(full.contains_key(&format!("color{}", i)));
+        }
+        assert_eq!(full["color0"], palette()["bg"]);
+        assert!(full.contains_key("accent"));
+        let mut missing = palette();
+        missing.remove("fg");
+        assert!(complete_palette(&missing).is_err());
+    }
+    #[test]
+    fn generates_renderable_themes() {
+        let home = env::temp_dir().join(format!("ravenlib-generate-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        let store = generate_theme_in(&paths, "night", &palette(), &["xres", "poly"]).unwrap();
+        assert_eq!(store.options, vec!["xres", "poly"]);
+        let poly = fs::read_to_string(paths.theme_file("night", "poly")).unwrap();
+        assert!(poly.contains("[bar/main]") && poly.contains("[bar/other]"));
+        for option in &["xres", "poly"] {
+            let contents = fs::read_to_string(paths.theme_file("night", option)).unwrap();
+            assert!(!render(&contents, &store.palette).unwrap().contains("{{"));
+        }
+        assert_eq!(get_config_in(&paths).unwrap().editing, "");
+        assert!(generate_theme_in(&paths, "day", &palette(), &["vim"]).is_err());
+        assert!(fs::metadata(paths.theme("day")).is_err());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use compose::setting_key;
+/// A section of an INI-style config, with its lines as they were written
+struct Section<'a> {
+    /// Name between the brackets, or None for the lines before the first section
+    name: Option<&'a str>,
+    header: Option<&'a str>,
+    lines: Vec<&'a str>,
+}
+impl<'a> Section<'a> {
+    /// Index just after the last line that isn't blank, so new keys go before the blank lines separating sections
+    fn insert_at(&self) -> usize {
+        self.lines
+            .iter()
+            .rposition(|x| !x.trim().is_empty())
+            .map(|x| x + 1)
+            .unwrap_or(0)
+    }
+}
+/// The name of a section header like `[colors]`
+fn section_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with('[') && line.ends_with(']') {
+        Some(line[1..line.len() - 1].trim())
+    } else {
+        None
+    }
+}
+fn parse<'a>(text: &'a str) -> Vec<Section<'a>> {
+    let mut sections = vec![Section {
+        name: None,
+        header: None,
+        lines: Vec::new(),
+    }];
+    for line in text.lines() {
+        match section_name(line) {
+            Some(name) => sections.push(Section {
+                name: Some(name),
+                header: Some(line),
+                lines: Vec::new(),
+            }),
+            None => sections.last_mut().unwrap().lines.push(line),
+        }
+    }
+    sections
+}
+/// Merges two INI-style configs like dunstrc or termite's config section by section and key by key, with settings from the theme replacing those of the base. Sections and keys only in the theme are added, and comments in the base are kept.
+pub fn merge_ini(base: &str, theme: &str) -> String {
+    let mut merged = parse(base);
+    for section in parse(theme) {
+        let values: Vec<(&str, &str)> = section
+            .lines
+            .iter()
+            .filter_map(|x| setting_key(x).map(|k| (k, *x)))
+            .collect();
+        let mut found = false;
+        let mut added: Vec<&str> = Vec::new();
+        for target in merged.iter_mut().filter(|x| x.name == section.name) {
+            found = true;
+            for line in target.lines.iter_mut() {
+                if let Some(k) = setting_key(line) {
+                    if let Some(&(_, value)) = values.iter().rev().find(|x| x.0 == k) {
+                        *line = value;
+                        added.push(k);
+                    }
+                }
+            }
+        }
+        if !found {
+            // Sections the base doesn't have go at the end, after a blank line
+            if let Some(last) = merged.last_mut() {
+                if last.lines.last().map(|x| !x.trim().is_empty()) == Some(true) {
+                    last.lines.push("");
+                }
+            }
+            merged.push(section);
+            continue;
+        }
+        // Keys the base doesn't have go at the end of its last section with the same name
+        let target = merged
+            .iter_mut()
+            .filter(|x| x.name == section.name)
+            .last()
+            .unwrap();
+        let at = target.insert_at();
+        let new: Vec<&str> = section
+            .lines
+            .iter()
+            .filter(|x| setting_key(x).map(|k| !added.contains(&k)).unwrap_or(false))
+            .cloned()
+            .collect();
+        let rest = target.lines.split_off(at);
+        target.lines.extend(new);
+        target.lines.extend(rest);
+    }
+    let mut text = String::new();
+    for section in merged {
+        if let Some(header) = section.header {
+            text = text + header + "\n";
+        }
+        for line in section.lines {
+            text = text + line + "\n";
+        }
+    }
+    text
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn replaces_keys_in_place() {
+        let base = "[global]\n# Font of notifications\nfont = Monospace 8\nwidth = 300\n\n[urgency_low]\nbackground = \"#000000\"\n";
+        let merged = merge_ini(base, "[global]\nfont = Hack 10\n");
+        assert_eq!(
+            merged,
+            "[global]\n# Font of notifications\nfont = Hack 10\nwidth = 300\n\n[urgency_low]\nbackground = \"#000000\"\n"
+        );
+    }
+    #[test]
+    fn adds_new_keys_and_sections() {
+        let merged = merge_ini(
+            "[global]\nfont = A\n\n[colors]\nforeground = #fff\n",
+            "[global]\nheight = 10\n[urgency_normal]\ntimeout = 5\n",
+        );
+        assert_eq!(
+            merged,
+            "[global]\nfont = A\nheight = 10\n\n[colors]\nforeground = #fff\n\n[urgency_normal]\ntimeout = 5\n"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use error::*;
+/// Lines that delimit the block raven manages inside a user's own config
+#[derive(Debug, Clone, PartialEq)]
+pub struct Markers {
+    pub start: String,
+    pub end: String,
+    /// A new block goes before the last occurrence of this, like a closing tag, instead of at the end of the file
+    pub anchor: Option<String>,
+}
+impl Markers {
+    /// Markers for configs with # comments, like i3, bspwm and dunst
+    pub fn hash() -> Markers {
+        Markers {
+            start: String::from("# >>> raven >>>"),
+            end: String::from("# <<< raven <<<"),
+            anchor: None,
+        }
+    }
+    /// Markers for XML configs, with new blocks going before the given closing tag
+    pub fn xml(anchor: impl Into<String>) -> Markers {
+        Markers {
+            start: String::from("<!-- >>> raven >>> -->"),
+            end: String::from("<!-- <<< raven <<< -->"),
+            anchor: Some(anchor.into()),
+        }
+    }
+    /// Finds the start of the block and the end of its closing marker line
+    fn find(&self, existing: &str) -> Result<Option<(usize, usize)>> {
+        let start = match existing.find(&self.start) {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        match existing[start..].find(&self.end) {
+            Some(end) => {
+                let end = start + end + self.end.len();
+                let end = if existing[end..].starts_with('\n') {
+                    end + 1
+                } else {
+                    end
+                };
+                Ok(Some((start, end)))
+            }
+            None => Err(ErrorKind::BrokenBlock(self.end.clone()).into()),
+        }
+    }
+}
+/// Puts contents between the markers inside an existing config, replacing a previous block if there is one. Everything outside the block is kept as it is.
+pub fn inject_block(existing: &str, contents: &str, markers: &Markers) -> Result<String> {
+    let mut block = markers.start.clone() + "\n" + contents;
+    if !block.ends_with('\n') {
+        block.push('\n');
+    }
+    block = block + &markers.end + "\n";
+    if let Some((start, end)) = markers.find(existing)? {
+        return Ok(existing[..start].to_string() + &block + &existing[end..]);
+    }
+    let at = markers
+        .anchor
+        .as_ref()
+        .and_then(|x| existing.rfind(x.as_str()))
+        .unwrap_or(existing.len());
+    let sep = if at > 0 && !existing[..at].ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    };
+    Ok(existing[..at].to_string() + sep + &block + &existing[at..])
+}
+/// Removes the block between the markers from a config, if there is one
+pub fn remove_block(existing: &str, markers: &Markers) -> Result<String> {
+    Ok(match markers.find(existing)? {
+        Some((start, end)) => existing[..start].to_string() + &existing[end..],
+        None => existing.to_string(),
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn injects_and_replaces_blocks() {
+        let injected = inject_block("font = mono", "color = red", &Markers::hash()).unwrap();
+        assert_eq!(
+            injected,
+            "font = mono\n# >>> raven >>>\ncolor = red\n# <<< raven <<<\n"
+        );
+        let replaced = inject_block(
+            &(injected + "size = 10\n"),
+            "color = blue\n",
+            &Markers::hash(),
+        )
+        .unwrap();
+        assert_eq!(
+            replaced,
+            "font = mono\n# >>> raven >>>\ncolor = blue\n# <<< raven <<<\nsize = 10\n"
+        );
+        assert_eq!(
+            remove_block(&replaced, &Markers::hash()).unwrap(),
+            "font = mono\nsize = 10\n"
+        );
+    }
+    #[test]
+    fn puts_xml_blocks_before_the_anchor() {
+        let markers = Markers::xml("</openbox_config>");
+        let injected = inject_block(
+            "<openbox_config>\n</openbox_config>\n",
+            "<theme/>",
+            &markers,
+        )
+        .unwrap();
+        assert_eq!(
+            injected,
+            "<openbox_config>\n<!-- >>> raven >>> -->\n<theme/>\n<!-- <<< raven <<< -->\n</openbox_config>\n"
+        );
+    }
+    #[test]
+    fn rejects_unclosed_blocks() {
+        let broken = "# >>> raven >>>\ncolor = red\n";
+        assert!(inject_block(broken, "", &Markers::hash()).is_err());
+        assert!(remove_block(broken, &Markers::hash()).is_err());
+    }
+}
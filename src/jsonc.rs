@@ -0,0 +1,455 @@
+use error::*;
+use serde_json::value::Value;
+use text::indent_of;
+/// A parsed value, with byte offsets into the source text
+enum Node {
+    Object {
+        start: usize,
+        /// Offset just after the closing brace
+        end: usize,
+        members: Vec<Member>,
+    },
+    Other {
+        start: usize,
+        end: usize,
+    },
+}
+impl Node {
+    fn span(&self) -> (usize, usize) {
+        match *self {
+            Node::Object { start, end, .. } | Node::Other { start, end } => (start, end),
+        }
+    }
+}
+/// A key and its value inside an object
+struct Member {
+    key: String,
+    /// Offset of the key's opening quote
+    start: usize,
+    value: Node,
+    /// Offset of the comma following the value, if any
+    comma: Option<usize>,
+}
+/// Parser for JSON with comments and trailing commas, that keeps track of where everything is
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        ErrorKind::InvalidJsonc(format!("{} at byte {}", message, self.pos)).into()
+    }
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).cloned()
+    }
+    /// Skips whitespace and comments
+    fn skip(&mut self) -> Result<()> {
+        loop {
+            let rest = &self.src[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                match comment.find("*/") {
+                    Some(i) => self.pos += i + 4,
+                    None => return Err(self.error("unterminated comment")),
+                }
+            } else if self.peek().map(|x| x.is_ascii_whitespace()) == Some(true) {
+                self.pos += 1;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+    /// Parses a string, returning its decoded contents
+    fn string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(serde_json::from_str(&self.src[start..self.pos])?);
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+    fn value(&mut self) -> Result<Node> {
+        self.skip()?;
+        let start = self.pos;
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => {
+                self.pos += 1;
+                loop {
+                    self.skip()?;
+                    match self.peek() {
+                        Some(b']') => break,
+                        Some(b',') => self.pos += 1,
+                        Some(_) => {
+                            self.value()?;
+                        }
+                        None => return Err(self.error("unterminated array")),
+                    }
+                }
+                self.pos += 1;
+                Ok(Node::Other {
+                    start,
+                    end: self.pos,
+                })
+            }
+            Some(b'"') => {
+                self.string()?;
+                Ok(Node::Other {
+                    start,
+                    end: self.pos,
+                })
+            }
+            Some(_) => {
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_whitespace() || b",]}/".contains(&c) {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let literal = &self.src[start..self.pos];
+                if !["true", "false", "null"].contains(&literal)
+                    && serde_json::from_str::<serde_json::Number>(literal).is_err()
+                {
+                    self.pos = start;
+                    return Err(self.error("expected a value"));
+                }
+                Ok(Node::Other {
+                    start,
+                    end: self.pos,
+                })
+            }
+            None => Err(self.error("expected a value")),
+        }
+    }
+    fn object(&mut self) -> Result<Node> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut members: Vec<Member> = Vec::new();
+        loop {
+            self.skip()?;
+            match self.peek() {
+                Some(b'}') => break,
+                Some(b',') => {
+                    if let Some(last) = members.last_mut() {
+                        last.comma = Some(self.pos);
+                    }
+                    self.pos += 1;
+                }
+                Some(b'"') => {
+                    let key_start = self.pos;
+                    let key = self.string()?;
+                    self.skip()?;
+                    if self.peek() != Some(b':') {
+                        return Err(self.error("expected a colon"));
+                    }
+                    self.pos += 1;
+                    let value = self.value()?;
+                    members.push(Member {
+                        key,
+                        start: key_start,
+                        value,
+                        comma: None,
+                    });
+                }
+                _ => return Err(self.error("expected a key")),
+            }
+        }
+        self.pos += 1;
+        Ok(Node::Object {
+            start,
+            end: self.pos,
+            members,
+        })
+    }
+}
+/// Parses the root object, or None if the text has nothing but whitespace and comments
+fn parse(text: &str) -> Result<Option<Node>> {
+    let mut parser = Parser { src: text, pos: 0 };
+    parser.skip()?;
+    if parser.peek().is_none() {
+        return Ok(None);
+    }
+    if parser.peek() != Some(b'{') {
+        return Err(parser.error("expected an object"));
+    }
+    let root = parser.value()?;
+    parser.skip()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected content after the root object"));
+    }
+    Ok(Some(root))
+}
+/// Finds the member with the given path
+fn find<'a>(root: &'a Node, path: &[&str]) -> Option<&'a Member> {
+    let mut node = root;
+    let mut found = None;
+    for key in path {
+        match *node {
+            Node::Object { ref members, .. } => {
+                let member = members.iter().rev().find(|x| x.key == *key)?;
+                node = &member.value;
+                found = Some(member);
+            }
+            _ => return None,
+        }
+    }
+    found
+}
+/// End of a // comment following the given offset on the same line, or the offset itself if there's none
+fn trailing_comment(text: &str, pos: usize) -> usize {
+    let line = &text[pos..text[pos..]
+        .find('\n')
+        .map(|x| x + pos)
+        .unwrap_or(text.len())];
+    if line.trim_start().starts_with("//") {
+        pos + line.len()
+    } else {
+        pos
+    }
+}
+/// The indentation one level adds, judging by a member of the object starting at the given offset
+fn unit_of(text: &str, object: usize, member: Option<usize>) -> String {
+    let outer = indent_of(text, object);
+    match member.map(|x| indent_of(text, x)) {
+        Some(inner) if inner.len() > outer.len() && inner.starts_with(outer) => {
+            inner[outer.len()..].to_string()
+        }
+        _ if outer.contains('\t') => String::from("\t"),
+        _ => String::from("    "),
+    }
+}
+/// Serializes the remaining path and value into a member, with nested objects indented one level further each
+fn member_text(path: &[&str], value: &Value, indent: &str, unit: &str) -> Result<String> {
+    let key = serde_json::to_string(path[0])?;
+    if path.len() == 1 {
+        return Ok(key + ": " + &serde_json::to_string(value)?);
+    }
+    let inner = indent.to_string() + unit;
+    Ok(key
+        + ": {\n"
+        + &inner
+        + &member_text(&path[1..], value, &inner, unit)?
+        + "\n"
+        + indent
+        + "}")
+}
+/// Gets the value at a path of keys, like ["editor", "fontSize"]
+pub fn get_key(text: &str, path: &[&str]) -> Result<Option<Value>> {
+    Ok(match parse(text)? {
+        Some(root) => match find(&root, path) {
+            Some(member) => {
+                let (start, end) = member.value.span();
+                serde_json::from_str(&text[start..end]).ok()
+            }
+            None => None,
+        },
+        None => None,
+    })
+}
+/// Sets the value at a path of keys, creating missing objects along the way. Comments and formatting outside the changed value are kept.
+pub fn set_key(text: &str, path: &[&str], value: &Value) -> Result<String> {
+    if path.is_empty() {
+        return Err(ErrorKind::InvalidJsonc(String::from("empty key path")).into());
+    }
+    let root = match parse(text)? {
+        Some(root) => root,
+        None => {
+            return Ok(text.to_string()
+                + "{\n    "
+                + &member_text(path, value, "    ", "    ")?
+                + "\n}\n")
+        }
+    };
+    let mut node = &root;
+    let mut depth = 0;
+    loop {
+        let (start, end, members) = match *node {
+            Node::Object {
+                start,
+                end,
+                ref members,
+            } => (start, end, members),
+            Node::Other { .. } => unreachable!(),
+        };
+        match members.iter().rev().find(|x| x.key == path[depth]) {
+            Some(member) => match member.value {
+                Node::Object { .. } if depth + 1 < path.len() => {
+                    node = &member.value;
+                    depth += 1;
+                }
+                _ => {
+                    // Replace the value, or a non-object in the way of the path, wholesale
+                    let (vstart, vend) = member.value.span();
+                    let indent = indent_of(text, member.start);
+                    let unit = unit_of(text, start, Some(member.start));
+                    let new = member_text(&path[depth..], value, indent, &unit)?;
+                    let new = &new[serde_json::to_string(path[depth])?.len() + 2..];
+                    return Ok(text[..vstart].to_string() + new + &text[vend..]);
+                }
+            },
+            None => {
+                let close = end - 1;
+                let parent = indent_of(text, start);
+                let unit = unit_of(text, start, members.last().map(|x| x.start));
+                let unit = unit.as_str();
+                return Ok(match members.last() {
+                    Some(last) => {
+                        let indent = indent_of(text, last.start);
+                        let member = member_text(&path[depth..], value, indent, unit)?;
+                        let (_, vend) = last.value.span();
+                        let after = last.comma.map(|x| x + 1).unwrap_or(vend);
+                        let comma = if last.comma.is_some() { "" } else { "," };
+                        // Put the new member after anything else on the last member's line, like a comment
+                        let line_end = text[after..]
+                            .find('\n')
+                            .map(|x| x + after)
+                            .unwrap_or(text.len());
+                        if line_end < close {
+                            text[..vend].to_string()
+                                + comma
+                                + &text[vend..line_end]
+                                + "\n"
+                                + indent
+                                + &member
+                                + &text[line_end..]
+                        } else {
+                            text[..vend].to_string() + comma + " " + &member + &text[vend..]
+                        }
+                    }
+                    None => {
+                        let indent = parent.to_string() + unit;
+                        let member = member_text(&path[depth..], value, &indent, unit)?;
+                        let tail = if text[start + 1..close].contains('\n') {
+                            String::new()
+                        } else {
+                            String::from("\n") + parent
+                        };
+                        text[..start + 1].to_string()
+                            + "\n"
+                            + &indent
+                            + &member
+                            + &tail
+                            + &text[start + 1..]
+                    }
+                });
+            }
+        }
+    }
+}
+/// Removes the member at a path of keys, along with its comma and a comment following it on its line. Returns the text unchanged if it doesn't exist.
+pub fn remove_key(text: &str, path: &[&str]) -> Result<String> {
+    let root = match parse(text)? {
+        Some(root) => root,
+        None => return Ok(text.to_string()),
+    };
+    let parent = if path.len() > 1 {
+        match find(&root, &path[..path.len() - 1]) {
+            Some(member) => &member.value,
+            None => return Ok(text.to_string()),
+        }
+    } else {
+        &root
+    };
+    let members = match *parent {
+        Node::Object { ref members, .. } => members,
+        Node::Other { .. } => return Ok(text.to_string()),
+    };
+    let i = match members.iter().rposition(|x| x.key == path[path.len() - 1]) {
+        Some(i) => i,
+        None => return Ok(text.to_string()),
+    };
+    let member = &members[i];
+    let (_, vend) = member.value.span();
+    let line_start = text[..member.start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let own_line = text[line_start..member.start].trim().is_empty();
+    let (start, end) = match member.comma {
+        Some(comma) => {
+            let (start, end) = if own_line {
+                (line_start, trailing_comment(text, comma + 1))
+            } else {
+                (member.start, comma + 1)
+            };
+            let rest = &text[end..];
+            let end = match rest.find('\n') {
+                Some(n) if rest[..n].trim().is_empty() && own_line => end + n + 1,
+                _ => end,
+            };
+            (start, end)
+        }
+        None if i > 0 => {
+            // Drop the previous member's comma, keeping anything after it like comments
+            let (start, end) = if own_line && line_start > 0 {
+                (line_start - 1, trailing_comment(text, vend))
+            } else {
+                (member.start, vend)
+            };
+            return Ok(match members[i - 1].comma {
+                Some(comma) => text[..comma].to_string() + &text[comma + 1..start] + &text[end..],
+                None => text[..start].to_string() + &text[end..],
+            });
+        }
+        None => (parent.span().0 + 1, trailing_comment(text, vend)),
+    };
+    Ok(text[..start].to_string() + &text[end..])
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const SETTINGS: &str = "{\n    // \"editor.fontSize\": 10,\n    \"editor.fontSize\": 12, // larger\n    \"workbench\": {\n        \"colorTheme\": \"Dark\",\n    },\n}\n";
+    #[test]
+    fn gets_keys_ignoring_comments() {
+        assert_eq!(
+            get_key(SETTINGS, &["editor.fontSize"]).unwrap(),
+            Some(json!(12))
+        );
+        assert_eq!(
+            get_key(SETTINGS, &["workbench", "colorTheme"]).unwrap(),
+            Some(json!("Dark"))
+        );
+        assert_eq!(get_key(SETTINGS, &["missing"]).unwrap(), None);
+    }
+    #[test]
+    fn sets_keys_keeping_comments() {
+        let set = set_key(SETTINGS, &["workbench", "colorTheme"], &json!("Light")).unwrap();
+        assert_eq!(set, SETTINGS.replace("\"Dark\"", "\"Light\""));
+        let added = set_key("{\n    \"a\": 1 // one\n}\n", &["b", "c"], &json!(true)).unwrap();
+        assert_eq!(
+            added,
+            "{\n    \"a\": 1, // one\n    \"b\": {\n        \"c\": true\n    }\n}\n"
+        );
+        assert_eq!(
+            set_key("", &["a"], &json!(1)).unwrap(),
+            "{\n    \"a\": 1\n}\n"
+        );
+    }
+    #[test]
+    fn removes_keys_with_their_comments() {
+        let removed = remove_key(SETTINGS, &["editor.fontSize"]).unwrap();
+        assert_eq!(
+            removed,
+            "{\n    // \"editor.fontSize\": 10,\n    \"workbench\": {\n        \"colorTheme\": \"Dark\",\n    },\n}\n"
+        );
+        let last = remove_key("{\n    \"a\": 1,\n    \"b\": 2 // two\n}\n", &["b"]).unwrap();
+        assert_eq!(last, "{\n    \"a\": 1\n}\n");
+    }
+    #[test]
+    fn rejects_invalid_literals() {
+        assert!(get_key("{\"a\": tru€}", &["a"]).is_err());
+        assert!(get_key("{\"a\": nul}", &["a"]).is_err());
+        assert!(get_key("{\"a\": [true, null, -1.5e3]}", &["a"]).is_ok());
+    }
+    #[test]
+    fn rejects_trailing_content() {
+        assert!(get_key("{} x", &["a"]).is_err());
+        assert!(get_key("{}}", &["a"]).is_err());
+        assert!(get_key("{} // done\n/* really */\n", &["a"]).is_ok());
+    }
+}
@@ -1,10 +1,12 @@
 //! # ravenlib
 //! This powers [raven](https://git.sr.ht/~nicohman/raven), and provides an API for managing raven themes. Check raven for reasonably good example code.
+extern crate serde;
 #[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 extern crate dirs;
+extern crate image;
 extern crate multipart;
 extern crate reqwest;
 extern crate tar;
@@ -12,23 +14,77 @@ extern crate tar;
 extern crate error_chain;
 #[macro_use]
 extern crate log;
+/// Side effects of loading themes, and plans of them
+pub mod actions;
+/// Backups of files changed by themes
+pub mod backup;
+/// Capture of the current desktop's configs into themes
+pub mod capture;
+/// Composition of option files with base files
+pub mod compose;
+/// Color contrast checks of themes
+pub mod contrast;
+/// Detection of installed and running programs
+pub mod environment;
 pub mod error;
+/// Generation of palettes from images
+pub mod extract;
+/// Generation of themes from palettes
+pub mod generate;
+/// Merging of INI-style configs
+pub mod ini;
+/// Injection of theme files into marked blocks of existing configs
+pub mod inject;
+/// Editing of JSON settings files that may have comments
+pub mod jsonc;
+/// Pluggable loaders for theme options
+pub mod loaders;
+/// Detection of connected monitors and placement of bars on them
+pub mod monitors;
+/// Theme color palettes
+pub mod palette;
+/// Resolution of raven's directories
+pub mod paths;
+/// Tracking of background processes started by themes
+pub mod processes;
 /// Interactions with online instances of ThemeHub
 pub mod ravenserver;
+/// Reports on the results of applying themes
+pub mod report;
+/// Checks of what themes need from the system
+pub mod requirements;
+/// Running of external commands
+pub mod runner;
+/// Rendering of palette placeholders in option files
+pub mod template;
+/// Helpers for working with the text of config files
+pub mod text;
+/// Checks of themes for problems
+pub mod validate;
+/// Merging of XML configs
+pub mod xml;
 use std::fs::DirEntry;
 /// Module for theme manipulation
 pub mod themes;
 /// Config module
 pub mod config {
     use crate::themes::*;
+    use compose::BaseSpec;
     use dirs::home_dir;
     use error::*;
+    use paths::RavenPaths;
     use serde_json::value::Map;
-    use std::{fs, fs::OpenOptions, io::Read, io::Write};
+    use std::{collections::BTreeMap, fs, fs::OpenOptions, io::Read, io::Write};
     /// Returns home directory as string
     pub fn get_home() -> String {
         return String::from(home_dir().unwrap().to_str().unwrap());
     }
+    /// Returns home directory as string, or an error if it can't be found
+    pub fn try_get_home() -> Result<String> {
+        home_dir()
+            .and_then(|x| x.to_str().map(|x| x.to_string()))
+            .ok_or_else(|| ErrorKind::NoHome.into())
+    }
     /// Default ravenserver host
     pub fn default_host() -> String {
         String::from("https://demenses.net")
@@ -37,6 +93,10 @@ pub mod config {
     pub fn default_screen() -> String {
         String::new()
     }
+    /// Default number of backup sets kept
+    pub fn default_backups() -> usize {
+        10
+    }
     /// Default raven theme description
     pub fn default_desc() -> String {
         String::from("A raven theme.")
@@ -51,6 +111,15 @@ pub mod config {
         pub editing: String,
         #[serde(default = "default_host")]
         pub host: String,
+        /// Options whose files go into a marked block of the user's existing config, instead of replacing it along with a base file
+        #[serde(default)]
+        pub injected: Vec<String>,
+        /// Base files that options are composed with, for options that don't use the default base_<option>
+        #[serde(default)]
+        pub bases: BTreeMap<String, Vec<BaseSpec>>,
+        /// How many backup sets of changed files are kept, the oldest being removed first. 0 turns backups off.
+        #[serde(default = "default_backups")]
+        pub backups: usize,
     }
     impl Config {
         /// Default method for config file
@@ -62,25 +131,33 @@ pub mod config {
                 last: "".to_string(),
                 editing: "".to_string(),
                 host: default_host(),
+                injected: Vec::new(),
+                bases: BTreeMap::new(),
+                backups: default_backups(),
             }
         }
     }
     /// Check to see if there are themes still using the old format, and convert them if so.
     pub fn check_themes() -> Result<()> {
-        let entries = get_themes()?;
+        let paths = RavenPaths::try_new()?;
+        let entries = get_themes_in(&paths)?;
         for entry in entries {
             info!("Checking theme {}", entry);
-            if fs::metadata(get_home() + "/.config/raven/themes/" + &entry + "/theme").is_ok() {
-                convert_theme(entry)?;
+            if fs::metadata(paths.theme_file(&entry, "theme")).is_ok() {
+                convert_theme_in(&paths, entry)?;
             }
         }
         Ok(())
     }
     /// Create base raven directories and config file(s)
     pub fn init() -> Result<()> {
-        if fs::metadata(get_home() + "/.config/raven/config").is_err() {
-            fs::create_dir(get_home() + "/.config/raven")?;
-            fs::create_dir(get_home() + "/.config/raven/themes")?;
+        init_in(&RavenPaths::try_new()?)
+    }
+    /// Create base raven directories and config file(s) under the given paths
+    pub fn init_in(paths: &RavenPaths) -> Result<()> {
+        if fs::metadata(paths.file("config")).is_err() {
+            fs::create_dir_all(&paths.root)?;
+            fs::create_dir_all(paths.themes())?;
         } else {
             error!(
                     "The config file format has changed. Please check ~/.config/raven/config.json to reconfigure raven."
@@ -90,7 +167,7 @@ pub mod config {
         let mut file = OpenOptions::new()
             .create(true)
             .write(true)
-            .open(get_home() + "/.config/raven/config.json")?;
+            .open(paths.config_file())?;
         let default = serde_json::to_string(&Config::default())?;
         info!("Writing default config to file");
         file.write_all(default.as_bytes())?;
@@ -99,35 +176,49 @@ pub mod config {
     }
     /// Checks to see if base config/directories need to be initialized
     pub fn check_init() -> bool {
-        fs::metadata(get_home() + "/.config/raven").is_err()
-            || fs::metadata(get_home() + "/.config/raven/config.json").is_err()
-            || fs::metadata(get_home() + "/.config/raven/themes").is_err()
+        RavenPaths::try_new()
+            .map(|paths| check_init_in(&paths))
+            .unwrap_or(true)
+    }
+    /// Checks to see if base config/directories under the given paths need to be initialized
+    pub fn check_init_in(paths: &RavenPaths) -> bool {
+        fs::metadata(&paths.root).is_err()
+            || fs::metadata(paths.config_file()).is_err()
+            || fs::metadata(paths.themes()).is_err()
     }
     /// Updates and replaces the stored config with a new config
     pub fn up_config(conf: Config) -> Result<Config> {
+        up_config_in(&RavenPaths::try_new()?, conf)
+    }
+    /// Updates and replaces the config stored under the given paths
+    pub fn up_config_in(paths: &RavenPaths, conf: Config) -> Result<Config> {
         info!("Opening and writing to temp config file");
         OpenOptions::new()
             .create(true)
             .write(true)
-            .open(get_home() + "/.config/raven/~config.json")?
+            .open(paths.file("~config.json"))?
             .write_all(serde_json::to_string(&conf)?.as_bytes())?;
         info!("Copying temp file to config file");
-        fs::copy(
-            get_home() + "/.config/raven/~config.json",
-            get_home() + "/.config/raven/config.json",
-        )?;
+        fs::copy(paths.file("~config.json"), paths.config_file())?;
         info!("Removing temp file");
-        fs::remove_file(get_home() + "/.config/raven/~config.json")?;
+        fs::remove_file(paths.file("~config.json"))?;
         Ok(conf)
     }
     /// Converts a theme from the old pipe-delineated format to the new json format
     pub fn convert_theme<N>(theme_name: N) -> Result<ThemeStore>
+    where
+        N: Into<String>,
+    {
+        convert_theme_in(&RavenPaths::try_new()?, theme_name)
+    }
+    /// Converts a theme stored under the given paths from the old format to the json format
+    pub fn convert_theme_in<N>(paths: &RavenPaths, theme_name: N) -> Result<ThemeStore>
     where
         N: Into<String>,
     {
         let theme_name = theme_name.into();
         let mut theme = String::new();
-        let otp = get_home() + "/.config/raven/themes/" + &theme_name + "/theme";
+        let otp = paths.theme_file(&theme_name, "theme");
         fs::File::open(&otp)
             .expect("Couldn't read theme")
             .read_to_string(&mut theme)?;
@@ -145,54 +236,75 @@ pub mod config {
             screenshot: default_screen(),
             description: default_desc(),
             kv: Map::new(),
+            extends: None,
+            palette: Default::default(),
+            bars: Default::default(),
         };
         OpenOptions::new()
             .create(true)
             .write(true)
-            .open(get_home() + "/.config/raven/themes/" + &theme_name + "/theme.json")?
+            .open(paths.theme_file(&theme_name, "theme.json"))?
             .write_all(serde_json::to_string(&themes)?.as_bytes())?;
         Ok(themes)
     }
     /// Retrieve config settings from file
     pub fn get_config() -> Result<Config> {
+        get_config_in(&RavenPaths::try_new()?)
+    }
+    /// Retrieve config settings from the config file under the given paths
+    pub fn get_config_in(paths: &RavenPaths) -> Result<Config> {
         let mut conf = String::new();
         info!("Opening and reading config file");
-        fs::File::open(get_home() + "/.config/raven/config.json")?.read_to_string(&mut conf)?;
+        fs::File::open(paths.config_file())?.read_to_string(&mut conf)?;
         info!("Parsing config file");
         Ok(serde_json::from_str(&conf)?)
     }
 }
 /// Ravend control
 pub mod daemon {
+    use actions::CommandSpec;
     use error::*;
+    use runner::{CommandRunner, SystemRunner};
     use std::process::Child;
-    use std::process::Command;
     /// Starts ravend
     pub fn start_daemon() -> Result<Child> {
         info!("Starting ravend");
-        let child = Command::new("sh").arg("-c").arg("ravend").spawn()?;
+        let child = daemon_command().command().spawn()?;
         info!("Started cycle daemon.");
         Ok(child)
     }
+    /// Starts ravend through the given runner, returning its pid
+    pub fn start_daemon_with(runner: &dyn CommandRunner) -> Result<u32> {
+        info!("Starting ravend");
+        let pid = runner.spawn(&daemon_command())?;
+        info!("Started cycle daemon.");
+        Ok(pid)
+    }
+    fn daemon_command() -> CommandSpec {
+        CommandSpec::new("sh").arg("-c").arg("ravend")
+    }
     /// Stops ravend
     pub fn stop_daemon() -> Result<()> {
+        stop_daemon_with(&SystemRunner)
+    }
+    /// Stops ravend through the given runner
+    pub fn stop_daemon_with(runner: &dyn CommandRunner) -> Result<()> {
         info!("Starting pkill command");
-        Command::new("pkill")
-            .arg("-SIGKILL")
-            .arg("ravend")
-            .output()?;
+        runner.output(&CommandSpec::new("pkill").arg("-SIGKILL").arg("ravend"))?;
         info!("Stopped cycle daemon.");
         Ok(())
     }
     /// Checks if the ravend daemon is running
     pub fn check_daemon() -> Result<bool> {
+        check_daemon_with(&SystemRunner)
+    }
+    /// Checks if the ravend daemon is running, listing processes through the given runner
+    pub fn check_daemon_with(runner: &dyn CommandRunner) -> Result<bool> {
         info!("Starting ps command");
-        let out = Command::new("ps").arg("aux").output()?;
-        let form_out = String::from_utf8_lossy(&out.stdout);
-        let line_num = form_out.lines().filter(|x| x.contains("ravend")).count();
+        let out = runner.output(&CommandSpec::new("ps").arg("aux"))?;
+        let line_num = out.stdout.lines().filter(|x| x.contains("ravend")).count();
         Ok(line_num > 0)
     }
-
 }
 
 /// Converts DirEntry into a fully processed file/directory name
@@ -0,0 +1,337 @@
+use error::*;
+use paths::RavenPaths;
+use requirements::Requirement;
+use std::sync::Arc;
+use themes::Theme;
+/// Loads a single kind of theme option, like polybar or i3
+pub trait OptionLoader: Send + Sync {
+    /// The option's name, as stored in theme.json
+    fn name(&self) -> &str;
+    /// Files inside the theme directory that the option consumes
+    fn files(&self) -> Vec<String> {
+        vec![self.name().to_string()]
+    }
+    /// Applies the option for the given theme. Returns false if it was skipped.
+    fn load(&self, theme: &Theme) -> Result<bool>;
+    /// Programs and directories the option needs to load
+    fn requirements(&self, _paths: &RavenPaths) -> Vec<Requirement> {
+        Vec::new()
+    }
+    /// Names of the processes of the program the option configures, for telling whether it's running
+    fn processes(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Reverts whatever load did. Returns false if there was nothing to revert.
+    fn unload(&self, _theme: &Theme) -> Result<bool> {
+        Ok(false)
+    }
+}
+/// A loader backed by one of Theme's own load methods
+struct Builtin {
+    name: &'static str,
+    /// Programs that load runs
+    programs: &'static [&'static str],
+    /// Directories load writes into, any of which will do. Relative to the config directory, or to home if starting with ~/.
+    dirs: &'static [&'static str],
+    /// Processes of the configured program
+    processes: &'static [&'static str],
+    load: fn(&Theme) -> Result<bool>,
+    /// Reverts what load did, for options that need more than restoring backed up files
+    unload: Option<fn(&Theme) -> Result<bool>>,
+}
+impl OptionLoader for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn load(&self, theme: &Theme) -> Result<bool> {
+        (self.load)(theme)
+    }
+    fn unload(&self, theme: &Theme) -> Result<bool> {
+        match self.unload {
+            Some(unload) => unload(theme),
+            None => Ok(false),
+        }
+    }
+    fn processes(&self) -> Vec<String> {
+        self.processes.iter().map(|x| x.to_string()).collect()
+    }
+    fn requirements(&self, paths: &RavenPaths) -> Vec<Requirement> {
+        let mut reqs: Vec<Requirement> = self
+            .programs
+            .iter()
+            .map(|x| Requirement::Program(x.to_string()))
+            .collect();
+        reqs.extend(dir_requirement(paths, self.dirs));
+        reqs
+    }
+}
+/// A requirement for any of the given directories, which are relative to the config directory, or to home if starting with ~/
+fn dir_requirement(paths: &RavenPaths, dirs: &[&str]) -> Option<Requirement> {
+    if dirs.is_empty() {
+        return None;
+    }
+    Some(Requirement::Dir(
+        dirs.iter()
+            .map(|x| match x.strip_prefix("~/") {
+                Some(path) => paths.home_file(path),
+                None => paths.program(x),
+            })
+            .collect(),
+    ))
+}
+/// A key of a theme's key-value storage, whose value names something for a program to use, like a VSCode color theme
+#[derive(Clone, Copy)]
+pub struct KvKey {
+    pub name: &'static str,
+    /// Directories loading the value writes into, any of which will do
+    dirs: &'static [&'static str],
+    /// Processes of the configured program
+    processes: &'static [&'static str],
+    /// Applies a value of the key
+    pub load: fn(&Theme, &str) -> Result<bool>,
+    /// Reverts what load did
+    unload: Option<fn(&Theme) -> Result<bool>>,
+}
+/// Keys that are recognized in a theme's key-value storage
+pub const KV_KEYS: [KvKey; 4] = [
+    KvKey {
+        name: "st_tmtheme",
+        dirs: &["sublime-text-3/Packages/User"],
+        processes: &["sublime_text"],
+        load: |t, v| t.load_sublt("st_tmtheme", v),
+        unload: None,
+    },
+    KvKey {
+        name: "st_scs",
+        dirs: &["sublime-text-3/Packages/User"],
+        processes: &["sublime_text"],
+        load: |t, v| t.load_sublt("st_scs", v),
+        unload: None,
+    },
+    KvKey {
+        name: "st_subltheme",
+        dirs: &["sublime-text-3/Packages/User"],
+        processes: &["sublime_text"],
+        load: |t, v| t.load_sublt("st_subltheme", v),
+        unload: None,
+    },
+    KvKey {
+        name: "vscode",
+        dirs: &["Code/User", "Code - OSS/User"],
+        processes: &["code", "code-oss"],
+        load: |t, v| t.load_vscode(v),
+        unload: Some(|t| t.unload_vscode()),
+    },
+];
+/// Gets a recognized key of a theme's key-value storage
+pub fn kv_key(name: &str) -> Option<&'static KvKey> {
+    KV_KEYS.iter().find(|x| x.name == name)
+}
+/// As an option, a key converts an old single-file option into key-value storage
+impl OptionLoader for KvKey {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn load(&self, theme: &Theme) -> Result<bool> {
+        theme.convert_single(self.name)
+    }
+    fn unload(&self, theme: &Theme) -> Result<bool> {
+        match self.unload {
+            Some(unload) => unload(theme),
+            None => Ok(false),
+        }
+    }
+    fn processes(&self) -> Vec<String> {
+        self.processes.iter().map(|x| x.to_string()).collect()
+    }
+    fn requirements(&self, paths: &RavenPaths) -> Vec<Requirement> {
+        dir_requirement(paths, self.dirs).into_iter().collect()
+    }
+}
+/// Set of loaders that options are dispatched to, by name
+#[derive(Clone)]
+pub struct LoaderRegistry {
+    loaders: Vec<Arc<dyn OptionLoader>>,
+}
+impl LoaderRegistry {
+    /// Creates a registry without any loaders
+    pub fn empty() -> LoaderRegistry {
+        LoaderRegistry {
+            loaders: Vec::new(),
+        }
+    }
+    /// Adds a loader, replacing any already registered under the same name
+    pub fn register<L>(&mut self, loader: L)
+    where
+        L: OptionLoader + 'static,
+    {
+        let name = loader.name().to_string();
+        info!("Registering loader for option {}", name);
+        self.loaders.retain(|x| x.name() != name);
+        self.loaders.push(Arc::new(loader));
+    }
+    /// Gets the loader for an option
+    pub fn get(&self, name: &str) -> Option<Arc<dyn OptionLoader>> {
+        self.loaders.iter().find(|x| x.name() == name).cloned()
+    }
+    /// Names of all registered options
+    pub fn names(&self) -> Vec<String> {
+        self.loaders.iter().map(|x| x.name().to_string()).collect()
+    }
+    fn builtin(
+        &mut self,
+        name: &'static str,
+        programs: &'static [&'static str],
+        dirs: &'static [&'static str],
+        processes: &'static [&'static str],
+        load: fn(&Theme) -> Result<bool>,
+        unload: Option<fn(&Theme) -> Result<bool>>,
+    ) {
+        self.register(Builtin {
+            name,
+            programs,
+            dirs,
+            processes,
+            load,
+            unload,
+        });
+    }
+}
+impl Default for LoaderRegistry {
+    /// Creates a registry holding all of ravenlib's own loaders
+    fn default() -> LoaderRegistry {
+        let mut reg = LoaderRegistry::empty();
+        reg.builtin(
+            "poly",
+            &["polybar"],
+            &[],
+            &["polybar"],
+            |t: &Theme| t.load_poly(t.monitor),
+            Some(|t: &Theme| t.unload_components("poly")),
+        );
+        reg.builtin(
+            "wm",
+            &["i3-msg"],
+            &[],
+            &["i3"],
+            |t: &Theme| t.load_i3(true).map(|_| true),
+            Some(|t: &Theme| t.unload_i3(true).map(|_| true)),
+        );
+        reg.builtin(
+            "i3",
+            &["i3-msg"],
+            &[],
+            &["i3"],
+            |t: &Theme| t.load_i3(false).map(|_| true),
+            Some(|t: &Theme| t.unload_i3(false).map(|_| true)),
+        );
+        reg.builtin(
+            "xres",
+            &["xrdb"],
+            &[],
+            &[],
+            |t: &Theme| t.load_xres(false).map(|_| true),
+            Some(|t: &Theme| t.unload_xres("xres").map(|_| true)),
+        );
+        reg.builtin(
+            "xres_m",
+            &["xrdb"],
+            &[],
+            &[],
+            |t: &Theme| t.load_xres(true).map(|_| true),
+            Some(|t: &Theme| t.unload_xres("xres_m").map(|_| true)),
+        );
+        reg.builtin(
+            "pywal",
+            &["wal"],
+            &[],
+            &[],
+            |t: &Theme| t.load_pywal().map(|_| true),
+            None,
+        );
+        reg.builtin(
+            "wall",
+            &["feh"],
+            &[],
+            &[],
+            |t: &Theme| t.load_wall().map(|_| true),
+            None,
+        );
+        reg.builtin(
+            "ncmpcpp",
+            &[],
+            &["ncmpcpp", "~/.ncmpcpp"],
+            &["ncmpcpp"],
+            |t: &Theme| t.load_ncm(),
+            None,
+        );
+        reg.builtin(
+            "termite",
+            &["pkill"],
+            &["termite"],
+            &["termite"],
+            |t: &Theme| t.load_termite().map(|_| true),
+            Some(|t: &Theme| t.reload_termite().map(|_| true)),
+        );
+        reg.builtin(
+            "script",
+            &["sh"],
+            &[],
+            &[],
+            |t: &Theme| t.load_script().map(|_| true),
+            None,
+        );
+        reg.builtin(
+            "bspwm",
+            &["sh", "bspwm"],
+            &["bspwm"],
+            &["bspwm"],
+            |t: &Theme| t.load_bspwm().map(|_| true),
+            Some(|t: &Theme| t.unload_bspwm()),
+        );
+        reg.builtin(
+            "rofi",
+            &[],
+            &[],
+            &["rofi"],
+            |t: &Theme| t.load_rofi().map(|_| true),
+            None,
+        );
+        reg.builtin(
+            "ranger",
+            &[],
+            &["ranger"],
+            &["ranger"],
+            |t: &Theme| t.load_ranger().map(|_| true),
+            None,
+        );
+        reg.builtin(
+            "lemonbar",
+            &["sh", "lemonbar"],
+            &[],
+            &["lemonbar"],
+            |t: &Theme| t.load_lemon().map(|_| true),
+            Some(|t: &Theme| t.unload_components("lemonbar")),
+        );
+        reg.builtin(
+            "openbox",
+            &["openbox"],
+            &["openbox"],
+            &["openbox"],
+            |t: &Theme| t.load_openbox().map(|_| true),
+            Some(|t: &Theme| t.unload_openbox().map(|_| true)),
+        );
+        reg.builtin(
+            "dunst",
+            &["dunst"],
+            &["dunst"],
+            &["dunst"],
+            |t: &Theme| t.load_dunst().map(|_| true),
+            Some(|t: &Theme| t.unload_dunst()),
+        );
+        for key in KV_KEYS.iter() {
+            reg.register(*key);
+        }
+        reg
+    }
+}
@@ -0,0 +1,301 @@
+use actions::CommandSpec;
+use runner::{CommandRunner, SystemRunner};
+use std::{fs, io::Read};
+/// A connected output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// Output name, like HDMI-1, which polybar reads from $MONITOR
+    pub name: String,
+    pub primary: bool,
+}
+/// Which polybar bars go on which screens, declared in theme.json
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BarLayout {
+    /// Bars started on the primary screen
+    #[serde(default)]
+    pub primary: Vec<String>,
+    /// Bars started on every other screen
+    #[serde(default)]
+    pub secondary: Vec<String>,
+}
+impl BarLayout {
+    pub fn is_empty(&self) -> bool {
+        self.primary.is_empty() && self.secondary.is_empty()
+    }
+}
+/// A bar to start, and the output it goes on if outputs were detected
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarAssignment {
+    pub bar: String,
+    pub monitor: Option<String>,
+}
+/// Bars to start for a theme, and the ones it asks for that its polybar config doesn't define
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarSelection {
+    /// Bars to give each monitor in turn, when there's no layout
+    pub order: Vec<String>,
+    pub layout: BarLayout,
+    pub missing: Vec<String>,
+}
+/// Names of the bars defined by `[bar/name]` sections of a polybar config
+pub fn bar_names(config: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in config.lines() {
+        let line = line.trim();
+        if line.starts_with("[bar/") && line.ends_with(']') {
+            let name = line[5..line.len() - 1].trim().to_string();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+/// Chooses bars from those a polybar config defines. The bars of the fallback list that it defines are used in order, or all of its own bars if it defines none of them. If the config has no bar sections, like when they're in included files, the fallback list and layout are trusted as they are.
+pub fn select_bars(config: &str, layout: &BarLayout, fallback: &[String]) -> BarSelection {
+    let defined = bar_names(config);
+    if defined.is_empty() {
+        return BarSelection {
+            order: fallback.to_vec(),
+            layout: layout.clone(),
+            missing: Vec::new(),
+        };
+    }
+    let mut missing: Vec<String> = Vec::new();
+    let mut keep = |bars: &[String]| -> Vec<String> {
+        let mut kept = Vec::new();
+        for bar in bars {
+            if defined.contains(bar) {
+                kept.push(bar.clone());
+            } else if !missing.contains(bar) {
+                missing.push(bar.clone());
+            }
+        }
+        kept
+    };
+    let selected = BarLayout {
+        primary: keep(&layout.primary),
+        secondary: keep(&layout.secondary),
+    };
+    let mut order = if layout.is_empty() {
+        keep(fallback)
+    } else {
+        fallback
+            .iter()
+            .filter(|x| defined.contains(x))
+            .cloned()
+            .collect()
+    };
+    if order.is_empty() {
+        order = defined;
+    }
+    BarSelection {
+        order,
+        layout: selected,
+        missing,
+    }
+}
+/// Finds connected outputs in the output of `xrandr --query`. If none is marked primary, the first one is.
+pub fn parse_xrandr(output: &str) -> Vec<Monitor> {
+    let mut monitors: Vec<Monitor> = output
+        .lines()
+        .filter(|x| !x.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?;
+            if words.next() != Some("connected") {
+                return None;
+            }
+            Some(Monitor {
+                name: name.to_string(),
+                primary: words.next() == Some("primary"),
+            })
+        })
+        .collect();
+    mark_primary(&mut monitors);
+    monitors
+}
+/// Counts connected outputs from the status files in a directory like /sys/class/drm. Their names are those of the kernel, like HDMI-A-1, which don't match xrandr's, so only the count is used.
+pub fn drm_monitor_count(drm_dir: &str) -> usize {
+    let entries = match fs::read_dir(drm_dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|x| x.ok())
+        // Connectors are named like card0-HDMI-A-1
+        .filter(|x| x.file_name().to_string_lossy().contains('-'))
+        .filter(|x| {
+            let mut status = String::new();
+            fs::File::open(x.path().join("status"))
+                .and_then(|mut f| f.read_to_string(&mut status))
+                .is_ok()
+                && status.trim() == "connected"
+        })
+        .count()
+}
+fn mark_primary(monitors: &mut [Monitor]) {
+    if !monitors.iter().any(|x| x.primary) {
+        if let Some(first) = monitors.first_mut() {
+            first.primary = true;
+        }
+    }
+}
+/// Detects connected outputs with xrandr
+pub fn detect_monitors() -> Vec<Monitor> {
+    detect_monitors_with(&SystemRunner)
+}
+/// Detects connected outputs by running xrandr through the given runner
+pub fn detect_monitors_with(runner: &dyn CommandRunner) -> Vec<Monitor> {
+    info!("Detecting connected monitors");
+    match runner.output(&CommandSpec::new("xrandr").arg("--query")) {
+        Ok(ref out) if out.success() => parse_xrandr(&out.stdout),
+        _ => Vec::new(),
+    }
+}
+/// Number of bars to start when xrandr can't name the outputs: the number of connected outputs in the drm directory, or the given count if none are found there
+pub fn fallback_count(drm_dir: &str, count: i32) -> i32 {
+    match drm_monitor_count(drm_dir) {
+        0 => count,
+        found => {
+            info!("Found {} connected monitors in {}", found, drm_dir);
+            found as i32
+        }
+    }
+}
+/// Decides which bars to start on which monitors. With a layout, the primary monitor gets its primary bars and the rest its secondary ones. Otherwise each monitor gets the bar at its position in the fallback list, reusing the last one when there are more monitors than bars. Without any detected monitors, bars are started without a monitor: the layout's primary ones, or else the first count bars of the fallback list.
+pub fn assign_bars(
+    monitors: &[Monitor],
+    layout: &BarLayout,
+    fallback: &[String],
+    count: i32,
+) -> Vec<BarAssignment> {
+    if monitors.is_empty() && !layout.is_empty() {
+        return layout
+            .primary
+            .iter()
+            .map(|bar| BarAssignment {
+                bar: bar.clone(),
+                monitor: None,
+            })
+            .collect();
+    }
+    if monitors.is_empty() {
+        if count as usize > fallback.len() {
+            warn!(
+                "Config asks for {} monitors, but only {} bars are listed",
+                count,
+                fallback.len()
+            );
+        }
+        return fallback
+            .iter()
+            .take(count.max(0) as usize)
+            .map(|bar| BarAssignment {
+                bar: bar.clone(),
+                monitor: None,
+            })
+            .collect();
+    }
+    let mut ordered: Vec<&Monitor> = monitors.iter().filter(|x| x.primary).collect();
+    ordered.extend(monitors.iter().filter(|x| !x.primary));
+    let mut assigned = Vec::new();
+    for (i, monitor) in ordered.into_iter().enumerate() {
+        let bars: Vec<&String> = if !layout.is_empty() {
+            if i == 0 {
+                layout.primary.iter().collect()
+            } else {
+                layout.secondary.iter().collect()
+            }
+        } else {
+            fallback
+                .get(i)
+                .or_else(|| fallback.last())
+                .into_iter()
+                .collect()
+        };
+        for bar in bars {
+            assigned.push(BarAssignment {
+                bar: bar.clone(),
+                monitor: Some(monitor.name.clone()),
+            });
+        }
+    }
+    assigned
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const XRANDR: &str = "Screen 0: minimum 8 x 8, current 3840 x 1080, maximum 32767 x 32767\nDP-1 disconnected (normal left inverted right x axis y axis)\nHDMI-1 connected 1920x1080+0+0 (normal left inverted right x axis y axis) 531mm x 299mm\n   1920x1080     60.00*+\neDP-1 connected primary 1920x1080+1920+0 (normal left inverted right x axis y axis) 344mm x 193mm\n   1920x1080     60.01*+\n";
+    fn bars(names: &[&str]) -> Vec<String> {
+        names.iter().map(|x| x.to_string()).collect()
+    }
+    #[test]
+    fn parses_connected_outputs() {
+        assert_eq!(
+            parse_xrandr(XRANDR),
+            vec![
+                Monitor {
+                    name: String::from("HDMI-1"),
+                    primary: false,
+                },
+                Monitor {
+                    name: String::from("eDP-1"),
+                    primary: true,
+                },
+            ]
+        );
+        let first = parse_xrandr("VGA-1 connected 1024x768+0+0\n");
+        assert!(first[0].primary);
+    }
+    #[test]
+    fn selects_defined_bars() {
+        let config = "[colors]\nbg = #000\n[bar/main]\nwidth = 100%\n[bar/other]\n";
+        let layout = BarLayout {
+            primary: bars(&["main", "ghost"]),
+            secondary: bars(&["other"]),
+        };
+        let selection = select_bars(config, &layout, &bars(&["other", "missing"]));
+        assert_eq!(selection.layout.primary, bars(&["main"]));
+        assert_eq!(selection.layout.secondary, bars(&["other"]));
+        assert_eq!(selection.order, bars(&["other"]));
+        assert_eq!(selection.missing, bars(&["ghost"]));
+        let fallback = select_bars(config, &BarLayout::default(), &bars(&["missing"]));
+        assert_eq!(fallback.order, bars(&["main", "other"]));
+        assert_eq!(fallback.missing, bars(&["missing"]));
+        let included = select_bars("include-file = bars.ini\n", &layout, &bars(&["x"]));
+        assert_eq!(included.layout, layout);
+        assert!(included.missing.is_empty());
+    }
+    #[test]
+    fn assigns_bars_to_monitors() {
+        let monitors = parse_xrandr(XRANDR);
+        let layout = BarLayout {
+            primary: bars(&["main"]),
+            secondary: bars(&["other"]),
+        };
+        let assigned = assign_bars(&monitors, &layout, &[], 1);
+        assert_eq!(
+            assigned,
+            vec![
+                BarAssignment {
+                    bar: String::from("main"),
+                    monitor: Some(String::from("eDP-1")),
+                },
+                BarAssignment {
+                    bar: String::from("other"),
+                    monitor: Some(String::from("HDMI-1")),
+                },
+            ]
+        );
+        let counted = assign_bars(&[], &BarLayout::default(), &bars(&["a", "b", "c"]), 2);
+        assert_eq!(
+            counted
+                .iter()
+                .map(|x| x.bar.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["a", "b"]
+        );
+        assert!(counted.iter().all(|x| x.monitor.is_none()));
+    }
+}
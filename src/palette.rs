@@ -0,0 +1,193 @@
+use error::*;
+use paths::RavenPaths;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::{collections::BTreeMap, fs, io::Read};
+/// Named colors of a theme, like bg, fg, accent or color0 through color15
+pub type Palette = BTreeMap<String, Color>;
+/// A single rgb color, stored as a hex string like #1d1f21
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+impl Color {
+    /// Creates a color from its channels
+    pub fn new(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+    /// Parses #rgb or #rrggbb colors, with or without the leading #
+    pub fn parse(hex: &str) -> Option<Color> {
+        let hex = hex.trim().trim_start_matches('#');
+        if !hex.chars().all(|x| x.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize, len: usize| u8::from_str_radix(&hex[i * len..(i + 1) * len], 16);
+        match hex.len() {
+            3 => Some(Color::new(
+                channel(0, 1).ok()? * 17,
+                channel(1, 1).ok()? * 17,
+                channel(2, 1).ok()? * 17,
+            )),
+            6 => Some(Color::new(
+                channel(0, 2).ok()?,
+                channel(1, 2).ok()?,
+                channel(2, 2).ok()?,
+            )),
+            _ => None,
+        }
+    }
+    /// Formats the color as #rrggbb
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+    /// Converts the color into hue (0-360), saturation and lightness (0-1)
+    pub fn to_hsl(&self) -> (f64, f64, f64) {
+        let (r, g, b) = (
+            self.r as f64 / 255.0,
+            self.g as f64 / 255.0,
+            self.b as f64 / 255.0,
+        );
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        if max == min {
+            return (0.0, 0.0, l);
+        }
+        let d = max - min;
+        let s = if l > 0.5 {
+            d / (2.0 - max - min)
+        } else {
+            d / (max + min)
+        };
+        let h = if max == r {
+            (g - b) / d + if g < b { 6.0 } else { 0.0 }
+        } else if max == g {
+            (b - r) / d + 2.0
+        } else {
+            (r - g) / d + 4.0
+        };
+        (h * 60.0, s, l)
+    }
+    /// Creates a color from hue (0-360), saturation and lightness (0-1)
+    pub fn from_hsl(h: f64, s: f64, l: f64) -> Color {
+        let (s, l) = (clamp(s), clamp(l));
+        if s == 0.0 {
+            let v = (l * 255.0).round() as u8;
+            return Color::new(v, v, v);
+        }
+        let q = if l < 0.5 {
+            l * (1.0 + s)
+        } else {
+            l + s - l * s
+        };
+        let p = 2.0 * l - q;
+        let h = (h % 360.0 + 360.0) % 360.0 / 360.0;
+        let channel = |t: f64| {
+            let t = if t < 0.0 {
+                t + 1.0
+            } else if t > 1.0 {
+                t - 1.0
+            } else {
+                t
+            };
+            let v = if t < 1.0 / 6.0 {
+                p + (q - p) * 6.0 * t
+            } else if t < 0.5 {
+                q
+            } else if t < 2.0 / 3.0 {
+                p + (q - p) * (2.0 / 3.0 - t) * 6.0
+            } else {
+                p
+            };
+            (v * 255.0).round() as u8
+        };
+        Color::new(channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0))
+    }
+    /// Lowers lightness by the given amount (0-1)
+    pub fn darken(&self, amount: f64) -> Color {
+        let (h, s, l) = self.to_hsl();
+        Color::from_hsl(h, s, l - amount)
+    }
+    /// Raises lightness by the given amount (0-1)
+    pub fn lighten(&self, amount: f64) -> Color {
+        let (h, s, l) = self.to_hsl();
+        Color::from_hsl(h, s, l + amount)
+    }
+}
+/// Limits a value to 0-1
+fn clamp(v: f64) -> f64 {
+    v.clamp(0.0, 1.0)
+}
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Color, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        Color::parse(&hex).ok_or_else(|| de::Error::custom(format!("invalid color {}", hex)))
+    }
+}
+/// The parts of pywal's colors.json that make up a palette
+#[derive(Deserialize)]
+struct PywalColors {
+    special: BTreeMap<String, Color>,
+    colors: BTreeMap<String, Color>,
+}
+/// Where pywal caches the colors of the last wallpaper it ran on
+pub fn pywal_cache(paths: &RavenPaths) -> String {
+    paths.home_file(".cache/wal/colors.json")
+}
+/// Parses a pywal colors.json into a palette with bg, fg, cursor and color0 through color15
+pub fn parse_pywal(json: &str) -> Result<Palette> {
+    let pywal: PywalColors = serde_json::from_str(json)?;
+    let mut palette = Palette::new();
+    for (name, short) in &[
+        ("background", "bg"),
+        ("foreground", "fg"),
+        ("cursor", "cursor"),
+    ] {
+        if let Some(color) = pywal.special.get(*name) {
+            palette.insert(short.to_string(), *color);
+        }
+    }
+    palette.extend(pywal.colors);
+    Ok(palette)
+}
+/// Reads a pywal colors.json into a palette
+pub fn read_pywal(path: impl Into<String>) -> Result<Palette> {
+    let path = path.into();
+    let mut st = String::new();
+    info!("Opening and reading pywal colors {}", path);
+    fs::File::open(&path)?.read_to_string(&mut st)?;
+    parse_pywal(&st)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Color::parse("#ff8000"), Some(Color::new(255, 128, 0)));
+        assert_eq!(Color::parse("f80"), Some(Color::new(255, 136, 0)));
+        assert_eq!(Color::parse("#ggg"), None);
+        assert_eq!(Color::parse("#12345"), None);
+        assert_eq!(Color::new(255, 128, 0).to_hex(), "#ff8000");
+    }
+    #[test]
+    fn changes_lightness() {
+        let grey = Color::new(128, 128, 128);
+        assert_eq!(grey.darken(1.0), Color::new(0, 0, 0));
+        assert_eq!(grey.lighten(1.0), Color::new(255, 255, 255));
+        let (_, _, l) = grey.darken(0.1).to_hsl();
+        assert!((l - (grey.to_hsl().2 - 0.1)).abs() < 0.01);
+    }
+}
@@ -0,0 +1,104 @@
+use config::try_get_home;
+use error::*;
+use std::env;
+/// Resolved locations of raven's own directory and of the program configs it manages
+#[derive(Clone, Debug, PartialEq)]
+pub struct RavenPaths {
+    /// The user's home directory
+    pub home: String,
+    /// Base directory for program configs, usually ~/.config
+    pub config: String,
+    /// Raven's root directory, usually ~/.config/raven
+    pub root: String,
+    /// Where the kernel lists display outputs, usually /sys/class/drm
+    pub drm: String,
+}
+/// Reads an environment variable, treating empty values as unset
+fn env_dir(var: &str) -> Option<String> {
+    env::var(var)
+        .ok()
+        .map(|x| x.trim_end_matches('/').to_string())
+        .filter(|x| !x.is_empty())
+}
+/// The kernel's directory of display outputs
+fn default_drm() -> String {
+    String::from("/sys/class/drm")
+}
+impl RavenPaths {
+    /// Resolves all paths from the environment. $XDG_CONFIG_HOME replaces ~/.config, and $RAVEN_HOME overrides the raven root itself. Panics if there's neither a home directory nor $RAVEN_HOME, see try_new.
+    pub fn new() -> RavenPaths {
+        RavenPaths::try_new().expect("Couldn't resolve raven's directories")
+    }
+    /// Resolves all paths from the environment like new, returning an error if there's neither a home directory nor $RAVEN_HOME
+    pub fn try_new() -> Result<RavenPaths> {
+        if let Some(root) = env_dir("RAVEN_HOME") {
+            return Ok(RavenPaths::with_root(root));
+        }
+        let home = try_get_home()?;
+        let config = env_dir("XDG_CONFIG_HOME").unwrap_or(home.clone() + "/.config");
+        let root = config.clone() + "/raven";
+        Ok(RavenPaths {
+            home,
+            config,
+            root,
+            drm: default_drm(),
+        })
+    }
+    /// Uses the given directory as the raven root, resolving everything else from the environment. Without a home directory, the root stands in for it.
+    pub fn with_root(root: impl Into<String>) -> RavenPaths {
+        let root = root.into().trim_end_matches('/').to_string();
+        let home = try_get_home().unwrap_or_else(|_| root.clone());
+        let config = env_dir("XDG_CONFIG_HOME").unwrap_or(home.clone() + "/.config");
+        RavenPaths {
+            home,
+            config,
+            root,
+            drm: default_drm(),
+        }
+    }
+    /// Uses the given directory as home, with ~/.config and ~/.config/raven inside it, without reading the environment
+    pub fn with_home(home: impl Into<String>) -> RavenPaths {
+        let home = home.into().trim_end_matches('/').to_string();
+        let config = home.clone() + "/.config";
+        let root = config.clone() + "/raven";
+        RavenPaths {
+            home,
+            config,
+            root,
+            drm: default_drm(),
+        }
+    }
+    /// A file directly inside the raven root, like config.json or base_i3
+    pub fn file(&self, name: &str) -> String {
+        self.root.clone() + "/" + name
+    }
+    /// The config file
+    pub fn config_file(&self) -> String {
+        self.file("config.json")
+    }
+    /// The directory holding all themes
+    pub fn themes(&self) -> String {
+        self.file("themes")
+    }
+    /// The directory of a single theme
+    pub fn theme(&self, theme: &str) -> String {
+        self.themes() + "/" + theme
+    }
+    /// A file inside a theme's directory
+    pub fn theme_file(&self, theme: &str, file: &str) -> String {
+        self.theme(theme) + "/" + file
+    }
+    /// A path inside the base config directory, like i3/config
+    pub fn program(&self, path: &str) -> String {
+        self.config.clone() + "/" + path
+    }
+    /// A path inside the home directory, for programs that don't use ~/.config
+    pub fn home_file(&self, path: &str) -> String {
+        self.home.clone() + "/" + path
+    }
+}
+impl Default for RavenPaths {
+    fn default() -> RavenPaths {
+        RavenPaths::new()
+    }
+}
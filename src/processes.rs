@@ -0,0 +1,189 @@
+use actions::{CommandSpec, Executor};
+use error::*;
+use paths::RavenPaths;
+use std::{fs, fs::OpenOptions, io::Read, io::Write};
+/// A background process started by a theme option
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Component {
+    /// Option that started the process
+    pub option: String,
+    pub program: String,
+    pub pid: u32,
+    /// Process group of the process and anything it started
+    pub pgid: u32,
+    /// When the process started, in clock ticks after boot, to tell it apart from a later process with the same pid
+    #[serde(default)]
+    pub start_time: u64,
+}
+/// The fields of /proc/<pid>/stat after the process name, starting with the process state
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let mut stat = String::new();
+    fs::File::open(format!("/proc/{}/stat", pid))
+        .and_then(|mut x| x.read_to_string(&mut stat))
+        .ok()?;
+    // The process name in field 2 may contain spaces, but is always in parentheses
+    Some(
+        stat[stat.rfind(')')? + 1..]
+            .split_whitespace()
+            .map(|x| x.to_string())
+            .collect(),
+    )
+}
+/// When a process started, from field 22 of /proc/<pid>/stat
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    stat_fields(pid)?.get(19)?.parse().ok()
+}
+/// Whether a process exited without having been waited for
+pub fn is_zombie(pid: u32) -> bool {
+    stat_fields(pid)
+        .and_then(|x| x.first().map(|x| x == "Z"))
+        .unwrap_or(false)
+}
+impl Component {
+    /// Whether the process is still alive, and hasn't been replaced by another one with the same pid
+    pub fn is_running(&self) -> bool {
+        if self.pgid <= 1
+            || process_start_time(self.pid) != Some(self.start_time)
+            || is_zombie(self.pid)
+        {
+            return false;
+        }
+        let mut comm = String::new();
+        if fs::File::open(format!("/proc/{}/comm", self.pid))
+            .and_then(|mut x| x.read_to_string(&mut comm))
+            .is_err()
+        {
+            return false;
+        }
+        // The kernel cuts process names off at 15 characters
+        let comm = comm.trim();
+        !comm.is_empty()
+            && self
+                .program
+                .rsplit('/')
+                .next()
+                .unwrap_or("")
+                .starts_with(comm)
+    }
+}
+/// Background processes started by themes, stored in processes.json in the raven root
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProcessState {
+    pub components: Vec<Component>,
+}
+impl ProcessState {
+    /// Loads the process state, which is empty if it hasn't been stored yet
+    pub fn load_in(paths: &RavenPaths) -> Result<ProcessState> {
+        let mut st = String::new();
+        match fs::File::open(paths.file("processes.json")) {
+            Ok(mut file) => file.read_to_string(&mut st)?,
+            Err(_) => return Ok(ProcessState::default()),
+        };
+        Ok(serde_json::from_str(&st)?)
+    }
+    /// Writes the process state
+    pub fn store_in(&self, paths: &RavenPaths) -> Result<()> {
+        info!("Writing process state");
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(paths.file("processes.json"))?
+            .write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+/// Records a process started by an option, so it can be stopped on the next theme switch. Processes that already exited aren't recorded.
+pub fn track_component(
+    paths: &RavenPaths,
+    option: impl Into<String>,
+    program: impl Into<String>,
+    pid: u32,
+) -> Result<()> {
+    let start_time = match process_start_time(pid) {
+        Some(start_time) if pid > 1 => start_time,
+        _ => {
+            warn!("Not tracking process {}, which isn't running", pid);
+            return Ok(());
+        }
+    };
+    let mut state = ProcessState::load_in(paths)?;
+    let component = Component {
+        option: option.into(),
+        program: program.into(),
+        pid,
+        pgid: pid,
+        start_time,
+    };
+    info!("Tracking {:?}", component);
+    state.components.push(component);
+    state.store_in(paths)
+}
+/// Processes started by themes that are still running
+pub fn running_components() -> Result<Vec<Component>> {
+    running_components_in(&RavenPaths::try_new()?)
+}
+/// Processes started by themes in the given paths that are still running
+pub fn running_components_in(paths: &RavenPaths) -> Result<Vec<Component>> {
+    Ok(ProcessState::load_in(paths)?
+        .components
+        .into_iter()
+        .filter(|x| x.is_running())
+        .collect())
+}
+/// Stops the process groups of running components and forgets them, either all of them or only those of the given option. Returns how many were stopped.
+pub fn stop_components_in(
+    paths: &RavenPaths,
+    executor: &Executor,
+    option: Option<&str>,
+) -> Result<usize> {
+    let (running, kept): (Vec<Component>, Vec<Component>) = running_components_in(paths)?
+        .into_iter()
+        .partition(|x| option.map(|o| o == x.option).unwrap_or(true));
+    for component in &running {
+        // Signalling group 0 or 1 would reach raven itself or every process the user owns
+        if component.pgid <= 1 {
+            warn!("Not stopping process group {}", component.pgid);
+            continue;
+        }
+        info!(
+            "Stopping {} started by option {}",
+            component.program, component.option
+        );
+        executor.run(
+            CommandSpec::new("kill")
+                .arg("-TERM")
+                .arg("--")
+                .arg(format!("-{}", component.pgid)),
+        )?;
+        if !executor.is_dry_run() && !executor.runner().reap(component.pid) {
+            warn!("Process {} hasn't exited yet", component.pid);
+        }
+    }
+    if !executor.is_dry_run() {
+        ProcessState { components: kept }.store_in(paths)?;
+    }
+    Ok(running.len())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    #[test]
+    fn stopped_components_are_reaped() {
+        let root = env::temp_dir().join(format!("ravenlib-processes-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let paths = RavenPaths::with_home(root.to_string_lossy().into_owned());
+        fs::create_dir_all(&paths.root).unwrap();
+        let executor = Executor::new();
+        let pid = executor
+            .spawn(CommandSpec::new("sleep").arg("100"))
+            .unwrap()
+            .unwrap();
+        track_component(&paths, "wm", "sleep", pid).unwrap();
+        assert_eq!(running_components_in(&paths).unwrap().len(), 1);
+        assert_eq!(stop_components_in(&paths, &executor, None).unwrap(), 1);
+        assert!(process_start_time(pid).is_none());
+        assert!(running_components_in(&paths).unwrap().is_empty());
+    }
+}
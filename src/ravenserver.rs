@@ -1,6 +1,7 @@
 use config::*;
-use dirs::home_dir;
 use error::*;
+use paths::RavenPaths;
+use proc_path;
 use reqwest;
 use serde_json;
 use std::{
@@ -10,11 +11,8 @@ use std::{
     io,
     io::{Read, Write},
 };
-use tar::{Archive, Builder};
-use themes::ThemeStore;
-fn get_home() -> String {
-    return String::from(home_dir().unwrap().to_str().unwrap());
-}
+use tar::{Archive, Builder, Header};
+use themes::{flatten_chain, theme_chain_in, ThemeStore};
 /// Information on the currently logged in user
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserInfo {
@@ -31,10 +29,14 @@ pub struct MetaRes {
 }
 /// Loads in info on the currently logged in user
 pub fn load_info() -> Result<UserInfo> {
-    if fs::metadata(get_home() + "/.config/raven/ravenserver.json").is_ok() {
+    load_info_in(&RavenPaths::try_new()?)
+}
+/// Loads in info on the user logged in with the given paths
+pub fn load_info_in(paths: &RavenPaths) -> Result<UserInfo> {
+    if fs::metadata(paths.file("ravenserver.json")).is_ok() {
         let mut info = String::new();
         info!("Opening and reading user info file");
-        File::open(get_home() + "/.config/raven/ravenserver.json")?.read_to_string(&mut info)?;
+        File::open(paths.file("ravenserver.json"))?.read_to_string(&mut info)?;
         return Ok(serde_json::from_str(&info)?);
     } else {
         error!("Could not load user info");
@@ -43,12 +45,26 @@ pub fn load_info() -> Result<UserInfo> {
 }
 /// Exports a theme to a tar file, returning the file's name
 pub fn export<N>(theme_name: N, tmp: bool) -> Result<String>
+where
+    N: Into<String>,
+{
+    export_in(&RavenPaths::try_new()?, theme_name, tmp, false)
+}
+/// Exports a theme to a self-contained tar file that includes everything it inherits through extends
+pub fn export_flat<N>(theme_name: N, tmp: bool) -> Result<String>
+where
+    N: Into<String>,
+{
+    export_in(&RavenPaths::try_new()?, theme_name, tmp, true)
+}
+/// Exports a theme from the themes directory of the given paths to a tar file, returning the file's name. If flatten is set, the theme's extends chain is merged into the archive.
+pub fn export_in<N>(paths: &RavenPaths, theme_name: N, tmp: bool, flatten: bool) -> Result<String>
 where
     N: Into<String>,
 {
     info!("Exporting theme");
     let theme_name = theme_name.into();
-    if fs::metadata(get_home() + "/.config/raven/themes/" + &theme_name).is_ok() {
+    if fs::metadata(paths.theme(&theme_name)).is_ok() {
         let mut tname = String::new();
         if tmp {
             info!("Using temp directory /tmp");
@@ -58,11 +74,29 @@ where
         info!("Creating output file");
         let tb = File::create(&tname)?;
         let mut b = Builder::new(tb);
-        info!("Importing theme into tar builder");
-        b.append_dir_all(
-            theme_name.to_string(),
-            get_home() + "/.config/raven/themes/" + &theme_name,
-        )?;
+        if flatten {
+            info!("Flattening theme's extends chain into tar builder");
+            let stores = theme_chain_in(paths, &theme_name)?;
+            let mut dirs = vec![theme_name.clone()];
+            dirs.extend(stores.iter().filter_map(|x| x.extends.clone()));
+            let store = serde_json::to_string(&flatten_chain(stores))?;
+            let mut header = Header::new_gnu();
+            header.set_size(store.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            b.append_data(
+                &mut header,
+                theme_name.clone() + "/theme.json",
+                store.as_bytes(),
+            )?;
+            let mut added = vec![String::from("theme.json"), String::from("~theme.json")];
+            for dir in dirs {
+                append_new_files(&mut b, &paths.theme(&dir), "", &theme_name, &mut added)?;
+            }
+        } else {
+            info!("Importing theme into tar builder");
+            b.append_dir_all(theme_name.to_string(), paths.theme(&theme_name))?;
+        }
         b.into_inner()?;
         info!("Wrote theme to {}", tname);
         Ok(tname)
@@ -71,8 +105,37 @@ where
         Err(ErrorKind::InvalidThemeName(theme_name).into())
     }
 }
+/// Recursively adds the files in a theme directory to an archive, skipping any that were already added
+fn append_new_files(
+    b: &mut Builder<File>,
+    dir: &str,
+    rel: &str,
+    theme_name: &str,
+    added: &mut Vec<String>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir.to_string() + "/" + rel)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file = rel.to_string() + &proc_path(entry);
+        if path.is_dir() {
+            append_new_files(b, dir, &(file + "/"), theme_name, added)?;
+        } else if !added.contains(&file) {
+            info!("Adding {} from {}", file, dir);
+            b.append_path_with_name(&path, theme_name.to_string() + "/" + &file)?;
+            added.push(file);
+        }
+    }
+    Ok(())
+}
 /// Imports a theme from a tar file
 pub fn import<N>(file_name: N) -> Result<()>
+where
+    N: Into<String>,
+{
+    import_in(&RavenPaths::try_new()?, file_name)
+}
+/// Imports a theme from a tar file into the themes directory of the given paths
+pub fn import_in<N>(paths: &RavenPaths, file_name: N) -> Result<()>
 where
     N: Into<String>,
 {
@@ -82,15 +145,15 @@ where
     info!("Converting opened file to archive reader");
     let mut arch = Archive::new(fd);
     info!("Unpacking archive");
-    arch.unpack(get_home() + "/.config/raven/themes/")?;
+    arch.unpack(paths.themes())?;
     info!("Imported theme.");
     Ok(())
 }
 /// Replaces and updates a stored userinfo file
-fn up_info(inf: UserInfo) -> Result<()> {
+fn up_info(paths: &RavenPaths, inf: UserInfo) -> Result<()> {
     info!("Updating stored userinfo");
-    let winfpath = get_home() + "/.config/raven/~ravenserver.json";
-    let infpath = get_home() + "/.config/raven/ravenserver.json";
+    let winfpath = paths.file("~ravenserver.json");
+    let infpath = paths.file("ravenserver.json");
     info!("Opening temp file and writing to it");
     OpenOptions::new()
         .create(true)
@@ -106,14 +169,22 @@ fn up_info(inf: UserInfo) -> Result<()> {
 }
 /// Logs a user out by deleting the userinfo file
 pub fn logout() -> Result<()> {
+    logout_in(&RavenPaths::try_new()?)
+}
+/// Logs a user out by deleting the userinfo file in the given paths
+pub fn logout_in(paths: &RavenPaths) -> Result<()> {
     info!("Removing ravenserver config file");
-    fs::remove_file(get_home() + "/.config/raven/ravenserver.json")?;
+    fs::remove_file(paths.file("ravenserver.json"))?;
     println!("Successfully logged you out");
     Ok(())
 }
 /// Gets the configured ThemeHub host
 pub fn get_host() -> Result<String> {
-    let conf = get_config()?;
+    get_host_in(&RavenPaths::try_new()?)
+}
+/// Gets the ThemeHub host configured in the given paths
+pub fn get_host_in(paths: &RavenPaths) -> Result<String> {
+    let conf = get_config_in(paths)?;
     Ok(conf.host)
 }
 /// Makes a call to delete the currently logged in user. Requires password confirmation
@@ -201,8 +272,8 @@ where
 {
     let name = name.into();
     let info = load_info()?;
-    if fs::metadata(get_home() + "/.config/raven/themes/" + &name).is_ok() {
-        let tname = export(name.as_str(), true)?;
+    if fs::metadata(RavenPaths::try_new()?.theme(&name)).is_ok() {
+        let tname = export_flat(name.as_str(), true)?;
         info!("Creating multipart upload form");
         let form = reqwest::multipart::Form::new().file("fileupload", &tname)?;
         info!("Making upload post request");
@@ -368,6 +439,7 @@ where
     N: Into<String>,
 {
     let name = name.into();
+    let paths = RavenPaths::try_new()?;
     info!("Downloading theme {}", name);
     let mut tname = String::new();
     if check_tmp() {
@@ -408,9 +480,8 @@ where
                 st.description = meta.description;
                 info!("Updating local theme store");
                 st.store()?;
-                if fs::metadata(get_home() + "/.config/raven/themes/" + &name + "/script").is_ok()
-                    || fs::metadata(get_home() + "/.config/raven/themes/" + &name + "/lemonbar")
-                        .is_ok()
+                if fs::metadata(paths.theme_file(&name, "script")).is_ok()
+                    || fs::metadata(paths.theme_file(&name, "lemonbar")).is_ok()
                 {
                     info!("Theme has script or lemonbar. Printing higher warning");
                     if !force {
@@ -444,8 +515,8 @@ where
             st.description = meta.description;
             info!("Updating local theme store");
             st.store()?;
-            if fs::metadata(get_home() + "/.config/raven/themes/" + &name + "/script").is_ok()
-                || fs::metadata(get_home() + "/.config/raven/themes/" + &name + "/lemonbar").is_ok()
+            if fs::metadata(paths.theme_file(&name, "script")).is_ok()
+                || fs::metadata(paths.theme_file(&name, "lemonbar")).is_ok()
             {
                 info!("Theme has script or lemonbar. Printing higher warning");
 
@@ -471,15 +542,29 @@ where
 }
 /// Logs a user in and writes userinfo file to disk
 pub fn login_user(name: impl Into<String>, pass: impl Into<String>) -> Result<()> {
+    login_user_in(&RavenPaths::try_new()?, name, pass)
+}
+/// Logs a user in with the host configured in the given paths, and writes the userinfo file there
+pub fn login_user_in(
+    paths: &RavenPaths,
+    name: impl Into<String>,
+    pass: impl Into<String>,
+) -> Result<()> {
     let client = reqwest::Client::new();
     info!("Making login request");
     let mut res = client
-        .get(&(get_host()? + "/themes/user/login?name=" + &name.into() + "&pass=" + &pass.into()))
+        .get(
+            &(get_host_in(paths)?
+                + "/themes/user/login?name="
+                + &name.into()
+                + "&pass="
+                + &pass.into()),
+        )
         .send()?;
     if res.status().is_success() {
         info!("Successfully signed in. Writing login info to disk.");
         let info = res.json()?;
-        up_info(info)?;
+        up_info(paths, info)?;
         Ok(())
     } else {
         if res.status() == reqwest::StatusCode::FORBIDDEN {
@@ -0,0 +1,67 @@
+use error::*;
+/// What happened to a single option or key-value entry while applying a theme
+#[derive(Debug)]
+pub enum OptionStatus {
+    /// The option was applied
+    Applied,
+    /// The option was not applied, for the given reason
+    Skipped(String),
+    /// Applying the option failed
+    Failed(Error),
+}
+/// The outcome of applying a single option or key-value entry
+#[derive(Debug)]
+pub struct OptionResult {
+    /// The option name or kv key
+    pub option: String,
+    pub status: OptionStatus,
+}
+/// Per-option results of applying a theme
+#[derive(Debug)]
+pub struct ApplyReport {
+    /// The name of the applied theme
+    pub theme: String,
+    /// Results in the order options were applied
+    pub results: Vec<OptionResult>,
+}
+impl ApplyReport {
+    /// Creates an empty report for the given theme
+    pub fn new(theme: impl Into<String>) -> ApplyReport {
+        ApplyReport {
+            theme: theme.into(),
+            results: Vec::new(),
+        }
+    }
+    /// Records the result of a single option
+    pub fn push(&mut self, option: impl Into<String>, status: OptionStatus) {
+        self.results.push(OptionResult {
+            option: option.into(),
+            status,
+        });
+    }
+    /// Results of options that were applied
+    pub fn applied(&self) -> Vec<&OptionResult> {
+        self.results
+            .iter()
+            .filter(|x| matches!(x.status, OptionStatus::Applied))
+            .collect()
+    }
+    /// Results of options that were skipped
+    pub fn skipped(&self) -> Vec<&OptionResult> {
+        self.results
+            .iter()
+            .filter(|x| matches!(x.status, OptionStatus::Skipped(_)))
+            .collect()
+    }
+    /// Results of options that failed
+    pub fn failed(&self) -> Vec<&OptionResult> {
+        self.results
+            .iter()
+            .filter(|x| matches!(x.status, OptionStatus::Failed(_)))
+            .collect()
+    }
+    /// Whether every option was applied or skipped without failing
+    pub fn is_ok(&self) -> bool {
+        self.failed().is_empty()
+    }
+}
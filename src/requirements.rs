@@ -0,0 +1,101 @@
+use environment::find_binary;
+use std::{env, fs};
+use themes::Theme;
+/// Something an option needs from the system to load
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// A program on $PATH
+    Program(String),
+    /// A directory, any of the given ones will do
+    Dir(Vec<String>),
+}
+impl Requirement {
+    /// Describes the requirement for error messages
+    pub fn describe(&self) -> String {
+        match *self {
+            Requirement::Program(ref program) => format!("program {}", program),
+            Requirement::Dir(ref dirs) => format!("directory {}", dirs.join(" or ")),
+        }
+    }
+}
+/// Whether a requirement is met, looking for programs in the given $PATH-style list
+pub fn is_met(requirement: &Requirement, path_var: &str) -> bool {
+    match *requirement {
+        Requirement::Program(ref program) => find_binary(path_var, program).is_some(),
+        Requirement::Dir(ref dirs) => dirs.iter().any(|x| fs::metadata(x).is_ok()),
+    }
+}
+/// A requirement of an option that isn't met
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingRequirement {
+    /// Option or kv key that needs it
+    pub option: String,
+    pub requirement: Requirement,
+}
+/// Finds everything the theme's options and kv keys need that's missing, before anything is applied
+pub fn check_theme_requirements(theme: &Theme) -> Vec<MissingRequirement> {
+    check_theme_requirements_with(theme, &env::var("PATH").unwrap_or_default())
+}
+/// Finds everything the theme's options and kv keys need that's missing, looking for programs in the given $PATH-style list
+pub fn check_theme_requirements_with(theme: &Theme, path_var: &str) -> Vec<MissingRequirement> {
+    info!("Checking requirements of theme {}", theme.name);
+    let mut missing = Vec::new();
+    for option in theme.option_names.iter().chain(theme.kv.keys()) {
+        let loader = match theme.loaders.get(option) {
+            Some(loader) => loader,
+            None => continue,
+        };
+        for requirement in loader.requirements(&theme.paths) {
+            if !is_met(&requirement, path_var) {
+                warn!("Option {} needs {}", option, requirement.describe());
+                missing.push(MissingRequirement {
+                    option: option.clone(),
+                    requirement,
+                });
+            }
+        }
+    }
+    missing
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::init_in;
+    use paths::RavenPaths;
+    use serde_json::value::Value;
+    use themes::{load_theme_in, new_theme_in, ThemeStore};
+    #[test]
+    fn finds_missing_programs_and_directories() {
+        let home = env::temp_dir().join(format!("ravenlib-requirements-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        let bin = paths.home_file("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.clone() + "/i3-msg", "").unwrap();
+        fs::create_dir_all(paths.home_file(".ncmpcpp")).unwrap();
+        fs::create_dir_all(paths.program("Code - OSS/User")).unwrap();
+        new_theme_in(&paths, "night").unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = vec![
+            String::from("wm"),
+            String::from("ncmpcpp"),
+            String::from("ranger"),
+        ];
+        store.kv.insert(
+            String::from("vscode"),
+            Value::String(String::from("Monokai")),
+        );
+        store.store_in(&paths).unwrap();
+        let theme = load_theme_in(&paths, "night").unwrap();
+        assert_eq!(
+            check_theme_requirements_with(&theme, &bin),
+            vec![MissingRequirement {
+                option: String::from("ranger"),
+                requirement: Requirement::Dir(vec![paths.program("ranger")]),
+            }]
+        );
+        assert!(!is_met(&Requirement::Program(String::from("i3-msg")), ""));
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
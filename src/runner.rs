@@ -0,0 +1,186 @@
+use actions::CommandSpec;
+use error::*;
+use std::{
+    os::unix::process::CommandExt,
+    process::{Child, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+/// Processes spawned by SystemRunner that haven't been waited for yet
+static CHILDREN: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+/// Waits for any spawned processes that already exited
+fn reap_exited(children: &mut Vec<Child>) {
+    children.retain_mut(|x| x.try_wait().map(|s| s.is_none()).unwrap_or(false));
+}
+/// What a finished command printed, and how it exited
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    /// Exit code, or None if the command was killed by a signal
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+impl CommandOutput {
+    /// Whether the command exited successfully
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+/// Runs external commands on behalf of ravenlib
+pub trait CommandRunner: Send + Sync {
+    /// Runs a command, waiting for it to finish
+    fn output(&self, spec: &CommandSpec) -> Result<CommandOutput>;
+    /// Starts a command in the background, returning its pid
+    fn spawn(&self, spec: &CommandSpec) -> Result<u32>;
+    /// Whether spawned pids belong to actual processes, that can be tracked and stopped later
+    fn spawns_processes(&self) -> bool {
+        false
+    }
+    /// Waits for a spawned process that was just stopped, so it doesn't linger as a zombie. Returns whether it exited.
+    fn reap(&self, _pid: u32) -> bool {
+        false
+    }
+}
+/// Runs commands as actual processes
+#[derive(Debug, Clone, Default)]
+pub struct SystemRunner;
+impl CommandRunner for SystemRunner {
+    fn output(&self, spec: &CommandSpec) -> Result<CommandOutput> {
+        let out = spec.command().output()?;
+        Ok(CommandOutput {
+            status: out.status.code(),
+            stdout: String::from_utf8_lossy(&out.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+        })
+    }
+    /// Spawned processes get their own process group, so they can be stopped along with anything they start
+    fn spawn(&self, spec: &CommandSpec) -> Result<u32> {
+        let child = spec
+            .command()
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+        let pid = child.id();
+        let mut children = CHILDREN.lock().unwrap();
+        reap_exited(&mut children);
+        children.push(child);
+        Ok(pid)
+    }
+    fn spawns_processes(&self) -> bool {
+        true
+    }
+    /// Gives the process a second to exit. Processes that take longer are reaped by a later spawn or reap.
+    fn reap(&self, pid: u32) -> bool {
+        let mut children = CHILDREN.lock().unwrap();
+        let reaped = match children.iter().position(|x| x.id() == pid) {
+            Some(i) => {
+                let mut exited = false;
+                for _ in 0..50 {
+                    exited = children[i].try_wait().map(|x| x.is_some()).unwrap_or(true);
+                    if exited {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(20));
+                }
+                exited
+            }
+            None => false,
+        };
+        // Exited processes, including this one, are forgotten here
+        reap_exited(&mut children);
+        reaped
+    }
+}
+/// Records commands instead of running them, for testing without the actual programs installed
+#[derive(Debug, Clone, Default)]
+pub struct RecordingRunner {
+    commands: Arc<Mutex<Vec<CommandSpec>>>,
+    outputs: Arc<Mutex<Vec<(String, CommandOutput)>>>,
+}
+impl RecordingRunner {
+    /// Creates a runner that answers every command with empty, successful output
+    pub fn new() -> RecordingRunner {
+        RecordingRunner::default()
+    }
+    /// Answers every later run of the given program with the given stdout
+    pub fn with_output(
+        self,
+        program: impl Into<String>,
+        stdout: impl Into<String>,
+    ) -> RecordingRunner {
+        self.outputs.lock().unwrap().push((
+            program.into(),
+            CommandOutput {
+                status: Some(0),
+                stdout: stdout.into(),
+                stderr: String::new(),
+            },
+        ));
+        self
+    }
+    /// All commands run or spawned so far, in order
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        self.commands.lock().unwrap().clone()
+    }
+    /// Whether a command with exactly the given program and arguments was run or spawned
+    pub fn ran(&self, program: &str, args: &[&str]) -> bool {
+        self.commands.lock().unwrap().iter().any(|x| {
+            x.program == program && x.args.iter().map(|a| a.as_str()).eq(args.iter().cloned())
+        })
+    }
+}
+impl CommandRunner for RecordingRunner {
+    fn output(&self, spec: &CommandSpec) -> Result<CommandOutput> {
+        info!("Recording command {:?}", spec);
+        self.commands.lock().unwrap().push(spec.clone());
+        Ok(self
+            .outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|x| x.0 == spec.program)
+            .map(|x| x.1.clone())
+            .unwrap_or(CommandOutput {
+                status: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            }))
+    }
+    fn spawn(&self, spec: &CommandSpec) -> Result<u32> {
+        info!("Recording spawned command {:?}", spec);
+        let mut commands = self.commands.lock().unwrap();
+        commands.push(spec.clone());
+        Ok(commands.len() as u32)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn recording_runner_answers_with_given_output() {
+        let runner = RecordingRunner::new().with_output("xrandr", "HDMI-1 connected\n");
+        let out = runner
+            .output(&CommandSpec::new("xrandr").arg("--query"))
+            .unwrap();
+        assert!(out.success());
+        assert_eq!(out.stdout, "HDMI-1 connected\n");
+        assert_eq!(
+            runner.output(&CommandSpec::new("i3-msg")).unwrap().stdout,
+            ""
+        );
+        assert!(runner.ran("xrandr", &["--query"]));
+        assert!(!runner.ran("xrandr", &[]));
+    }
+    #[test]
+    fn recording_runner_gives_spawns_distinct_pids() {
+        let runner = RecordingRunner::new();
+        let first = runner.spawn(&CommandSpec::new("dunst")).unwrap();
+        let second = runner.spawn(&CommandSpec::new("polybar")).unwrap();
+        assert_ne!(first, second);
+        assert!(!runner.spawns_processes());
+        assert_eq!(runner.commands().len(), 2);
+    }
+}
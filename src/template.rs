@@ -0,0 +1,160 @@
+use error::*;
+use palette::{Color, Palette};
+/// Whether a placeholder or filter name only uses allowed characters
+fn is_ident(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-')
+}
+/// Parses a filter amount into a fraction. It's either a percentage like 10% or a fraction like 0.1, anything outside of 0-100% is rejected.
+fn amount(arg: &str) -> Option<f64> {
+    let arg = arg.trim();
+    let (value, scale) = match arg.strip_suffix('%') {
+        Some(value) => (value.trim(), 100.0),
+        None => (arg, 1.0),
+    };
+    let value: f64 = value.parse().ok()?;
+    if (0.0..=scale).contains(&value) {
+        Some(value / scale)
+    } else {
+        None
+    }
+}
+/// Applies a single filter, like darken(10%), to a color. Returns the formatted result.
+fn apply_filter(color: Color, filter: &str) -> Result<(Color, Option<String>)> {
+    let filter = filter.trim();
+    let (name, arg) = match filter.find('(') {
+        Some(i) if filter.ends_with(')') => (&filter[..i], Some(&filter[i + 1..filter.len() - 1])),
+        _ => (filter, None),
+    };
+    let bad = || -> Error { ErrorKind::TemplateError(format!("invalid filter {}", filter)).into() };
+    match (name.trim(), arg) {
+        ("darken", Some(arg)) => Ok((color.darken(amount(arg).ok_or_else(bad)?), None)),
+        ("lighten", Some(arg)) => Ok((color.lighten(amount(arg).ok_or_else(bad)?), None)),
+        ("strip", None) => Ok((color, Some(color.to_hex()[1..].to_string()))),
+        ("rgb", None) => Ok((color, Some(format!("{},{},{}", color.r, color.g, color.b)))),
+        _ => Err(bad()),
+    }
+}
+/// Renders a single placeholder's contents, like `accent | darken(10%)`. Returns None if it isn't a placeholder at all, or names a color the palette doesn't have, like `${{HOME}}` in a shell script.
+fn render_placeholder(inner: &str, palette: &Palette) -> Result<Option<String>> {
+    let mut parts = inner.split('|');
+    let name = parts.next().unwrap_or("").trim();
+    if !is_ident(name) {
+        return Ok(None);
+    }
+    let mut color = match palette.get(name) {
+        Some(color) => *color,
+        None => return Ok(None),
+    };
+    let mut formatted = None;
+    for filter in parts {
+        if formatted.is_some() {
+            return Err(ErrorKind::TemplateError(format!(
+                "filter {} follows a formatting filter",
+                filter.trim()
+            ))
+            .into());
+        }
+        let (c, f) = apply_filter(color, filter)?;
+        color = c;
+        formatted = f;
+    }
+    Ok(Some(formatted.unwrap_or(color.to_hex())))
+}
+/// Replaces placeholders like `{{accent}}` or `{{color4 | darken(10%)}}` with colors from the palette. Anything between double braces that doesn't start with the name of a color is left alone.
+pub fn render(input: &str, palette: &Palette) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = input;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => match render_placeholder(&after[..end], palette)? {
+                Some(value) => {
+                    out.push_str(&value);
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    out.push_str("{{");
+                    rest = after;
+                }
+            },
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+/// Names of placeholders that look like colors but aren't in the palette, like a misspelled `{{acent}}`. Placeholders in shell variables like `${{HOME}}` aren't counted.
+pub fn unknown_placeholders(input: &str, palette: &Palette) -> Vec<String> {
+    let mut unknown: Vec<String> = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = input[pos..].find("{{").map(|x| x + pos) {
+        pos = start + 2;
+        let end = match input[pos..].find("}}") {
+            Some(end) => end + pos,
+            None => break,
+        };
+        let name = input[pos..end].split('|').next().unwrap_or("").trim();
+        if is_ident(name)
+            && !input[..start].ends_with('$')
+            && palette.get(name).is_none()
+            && !unknown.iter().any(|x| x == name)
+        {
+            unknown.push(name.to_string());
+        }
+    }
+    unknown
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn palette() -> Palette {
+        let mut palette = Palette::new();
+        palette.insert(String::from("accent"), Color::new(0x80, 0x80, 0x80));
+        palette
+    }
+    #[test]
+    fn renders_colors_and_filters() {
+        let palette = palette();
+        assert_eq!(
+            render("fg={{accent}} {{ accent | strip }}", &palette).unwrap(),
+            "fg=#808080 808080"
+        );
+        assert_eq!(render("{{accent|rgb}}", &palette).unwrap(), "128,128,128");
+        assert_eq!(
+            render("{{accent | darken(10%)}}", &palette).unwrap(),
+            render("{{accent | darken(0.1)}}", &palette).unwrap()
+        );
+    }
+    #[test]
+    fn leaves_other_braces_alone() {
+        let palette = palette();
+        let script = "echo ${{HOME}} {{ not a name }} {{unclosed";
+        assert_eq!(render(script, &palette).unwrap(), script);
+    }
+    #[test]
+    fn rejects_bad_filters() {
+        let palette = palette();
+        assert!(render("{{accent | darken(10)}}", &palette).is_err());
+        assert!(render("{{accent | darken(150%)}}", &palette).is_err());
+        assert!(render("{{accent | blur}}", &palette).is_err());
+        assert!(render("{{accent | strip | darken(10%)}}", &palette).is_err());
+    }
+    #[test]
+    fn finds_unknown_placeholders() {
+        let palette = palette();
+        assert_eq!(
+            unknown_placeholders(
+                "{{accent}} {{acent}} {{ acent | strip }} ${{HOME}} {{ not a name }}",
+                &palette
+            ),
+            vec![String::from("acent")]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+/// The spaces and tabs the line containing the given offset starts with
+pub fn indent_of(text: &str, pos: usize) -> &str {
+    let line = &text[text[..pos].rfind('\n').map(|x| x + 1).unwrap_or(0)..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
@@ -1,11 +1,25 @@
 use crate::config::*;
+use actions::{CommandSpec, Executor, PlannedAction};
+use backup::{list_backups_in, rollback_with, BackupSet};
+use compose::{bases_for, compose, find_base, BaseSpec, Placement};
+use environment::{detect_in, Environment};
 use error::*;
+use extract::{extract_palette, ExtractOptions};
+use inject::{inject_block, remove_block, Markers};
+use jsonc::{get_key, remove_key, set_key};
+use loaders::{kv_key, LoaderRegistry};
+use monitors::{assign_bars, detect_monitors_with, fallback_count, select_bars, BarLayout};
+use palette::{pywal_cache, read_pywal, Palette};
+use paths::RavenPaths;
 use proc_path;
+use processes::{stop_components_in, track_component};
+use report::{ApplyReport, OptionResult, OptionStatus};
 use serde_json::value::{Map, Value};
 use std::{
-    env, fs, fs::DirEntry, fs::OpenOptions, io, io::Read, io::Write, os::unix::fs::OpenOptionsExt,
-    process::Command,
+    collections::BTreeMap, env, fs, fs::DirEntry, fs::OpenOptions, io, io::Read, io::Write,
+    os::unix::fs::PermissionsExt,
 };
+use template::render;
 /// Structure for holding theme info, stored in theme.json
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ThemeStore {
@@ -18,21 +32,37 @@ pub struct ThemeStore {
     pub description: String,
     #[serde(default)]
     pub kv: Map<String, Value>,
+    /// Name of a theme whose options, files and key-values this theme inherits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Named colors that fill in placeholders like {{accent}} in option files
+    #[serde(default, skip_serializing_if = "Palette::is_empty")]
+    pub palette: Palette,
+    /// Polybar bars for the primary and other screens, in place of the config's list
+    #[serde(default, skip_serializing_if = "BarLayout::is_empty")]
+    pub bars: BarLayout,
 }
 impl ThemeStore {
     pub fn load(theme: impl Into<String>) -> Result<ThemeStore> {
+        ThemeStore::load_in(&RavenPaths::try_new()?, theme)
+    }
+    /// Loads a theme store from the themes directory of the given paths
+    pub fn load_in(paths: &RavenPaths, theme: impl Into<String>) -> Result<ThemeStore> {
         let theme = theme.into();
         let mut st = String::new();
         info!("Opening and reading theme store {}", theme);
-        fs::File::open(get_home() + "/.config/raven/themes/" + &theme + "/theme.json")?
-            .read_to_string(&mut st)?;
+        fs::File::open(paths.theme_file(&theme, "theme.json"))?.read_to_string(&mut st)?;
         info!("Parsing theme store");
         let result = serde_json::from_str(&st)?;
         Ok(result)
     }
     pub fn store(self) -> Result<ThemeStore> {
-        let wthemepath = get_home() + "/.config/raven/themes/" + &self.name + "/~theme.json";
-        let themepath = get_home() + "/.config/raven/themes/" + &self.name + "/theme.json";
+        self.store_in(&RavenPaths::try_new()?)
+    }
+    /// Writes the theme store into the themes directory of the given paths
+    pub fn store_in(self, paths: &RavenPaths) -> Result<ThemeStore> {
+        let wthemepath = paths.theme_file(&self.name, "~theme.json");
+        let themepath = paths.theme_file(&self.name, "theme.json");
         info!("Opening and writing to temp theme store");
         OpenOptions::new()
             .create(true)
@@ -51,12 +81,32 @@ impl ThemeStore {
 pub struct Theme {
     pub name: String,
     pub options: Vec<ROption>,
+    /// All option names from theme.json, including ones only known to custom loaders
+    pub option_names: Vec<String>,
     pub monitor: i32,
     pub enabled: Vec<String>,
     pub order: Vec<String>,
+    /// Options written into a marked block of the existing config instead of over it
+    pub injected: Vec<String>,
+    /// Base files configured for options, in place of the default base_<option>
+    pub bases: BTreeMap<String, Vec<BaseSpec>>,
     pub kv: Map<String, Value>,
     pub screenshot: String,
     pub description: String,
+    /// Named colors that option files are rendered with
+    pub palette: Palette,
+    /// Polybar bars for the primary and other screens
+    pub bars: BarLayout,
+    /// Where the theme and the configs it manages live
+    pub paths: RavenPaths,
+    /// The theme followed by every theme it extends, in order of precedence
+    pub chain: Vec<String>,
+    /// Loaders that options are dispatched to
+    pub loaders: LoaderRegistry,
+    /// Performs, or when dry-running records, the side effects of loading options
+    pub executor: Executor,
+    /// Installed programs. When set, options for missing programs are skipped.
+    pub environment: Option<Environment>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -95,27 +145,135 @@ impl ROption {
 }
 /// Methods for a loaded theme
 impl Theme {
-    /// Loads options held within theme.json key-value storage
-    pub fn load_kv(&self) {
+    /// Path of an option file, taken from the first theme in the extends chain that has it
+    pub fn theme_file(&self, file: &str) -> String {
+        self.chain
+            .iter()
+            .map(|x| self.paths.theme_file(x, file))
+            .find(|x| fs::metadata(x).is_ok())
+            .unwrap_or(self.paths.theme_file(&self.name, file))
+    }
+    /// Base files that exist for an option, in the order they're applied
+    pub fn option_bases(&self, option: &str) -> Vec<(String, Placement)> {
+        bases_for(&self.bases, option)
+            .into_iter()
+            .filter_map(|x| find_base(&self.paths, &x.file).map(|path| (path, x.placement)))
+            .collect()
+    }
+    /// Composes an option file with its base files if asked to, and fills in palette placeholders if the theme has a palette and the result has any. Returns None if the file is used as is, which files that aren't text always are.
+    fn process_option(&self, option: &str, with_bases: bool) -> Result<Option<String>> {
+        let mut contents = match String::from_utf8(fs::read(self.theme_file(option))?) {
+            Ok(contents) => contents,
+            Err(_) => {
+                info!("Option file {} isn't text, using it as is", option);
+                return Ok(None);
+            }
+        };
+        let bases = if with_bases {
+            self.option_bases(option)
+        } else {
+            Vec::new()
+        };
+        let composed = !bases.is_empty();
+        for (path, placement) in bases {
+            info!("Composing option {} with {}", option, path);
+            let mut base = String::new();
+            fs::File::open(&path)?.read_to_string(&mut base)?;
+            contents = compose(&base, &contents, placement)?;
+        }
+        if !self.palette.is_empty() && contents.contains("{{") {
+            info!("Rendering option file {}", option);
+            Ok(Some(render(&contents, &self.palette)?))
+        } else if composed {
+            Ok(Some(contents))
+        } else {
+            Ok(None)
+        }
+    }
+    /// Reads an option file, filling in palette placeholders if the theme has a palette
+    pub fn read_option(&self, file: &str) -> Result<String> {
+        match self.process_option(file, false)? {
+            Some(contents) => Ok(contents),
+            None => Ok(fs::read_to_string(self.theme_file(file))?),
+        }
+    }
+    /// Reads an option file composed with its base files, filling in palette placeholders if the theme has a palette
+    pub fn compose_option(&self, option: &str) -> Result<String> {
+        match self.process_option(option, true)? {
+            Some(contents) => Ok(contents),
+            None => Ok(fs::read_to_string(self.theme_file(option))?),
+        }
+    }
+    /// Copies an option file into place, composing it with its base files and filling in palette placeholders if there are any
+    pub fn install_option(&self, file: &str, target: impl Into<String>) -> Result<()> {
+        match self.process_option(file, true)? {
+            Some(contents) => self.executor.write(target, &contents, 0o666),
+            None => self.executor.copy(self.theme_file(file), target),
+        }
+    }
+    /// Path of an option file that a program reads directly. If the file has base files or palette placeholders, it's composed into the raven directory first.
+    pub fn option_path(&self, file: &str) -> Result<String> {
+        if fs::metadata(self.theme_file(file)).is_err() {
+            return Ok(self.theme_file(file));
+        }
+        let processed = self.process_option(file, true)?;
+        self.rendered_path(file, processed)
+    }
+    /// Writes a processed option file into the raven directory, returning its path there, or the option file's own path if it's used as is
+    fn rendered_path(&self, file: &str, processed: Option<String>) -> Result<String> {
+        let contents = match processed {
+            Some(contents) => contents,
+            None => return Ok(self.theme_file(file)),
+        };
+        let mode = fs::metadata(self.theme_file(file))?.permissions().mode();
+        self.executor.create_dir(self.paths.file("rendered"))?;
+        let target = self.paths.file("rendered") + "/" + file;
+        self.executor.write(target.as_str(), &contents, mode)?;
+        Ok(target)
+    }
+    /// Loads options held within theme.json key-value storage, returning the result of each key
+    pub fn load_kv(&self) -> Vec<OptionResult> {
         info!("Loading all key-value options");
+        let mut results = Vec::new();
         for (k, v) in &self.kv {
-            self.load_k(k.as_str(), v.as_str().unwrap()).unwrap();
+            if let Some(reason) = self.missing_reason(k) {
+                warn!("Skipping key {}: {}", k, reason);
+                results.push(OptionResult {
+                    option: k.clone(),
+                    status: OptionStatus::Skipped(reason),
+                });
+                continue;
+            }
+            let status = match v.as_str() {
+                Some(v) => match self.load_k(k.as_str(), v) {
+                    Ok(true) => OptionStatus::Applied,
+                    Ok(false) => OptionStatus::Skipped(format!("key {} was not applied", k)),
+                    Err(e) => {
+                        error!("Failed to load key {}: {}", k, e);
+                        OptionStatus::Failed(e)
+                    }
+                },
+                None => {
+                    error!("Value of key {} is not a string", k);
+                    OptionStatus::Failed(ErrorKind::InvalidKeyValue(k.clone()).into())
+                }
+            };
+            results.push(OptionResult {
+                option: k.clone(),
+                status,
+            });
         }
+        results
     }
     /// Loads a single key option
     pub fn load_k(&self, k: impl Into<String>, v: impl Into<String>) -> Result<bool> {
         let (k, v) = (k.into(), v.into());
         info!("Loading key {} with value {}", k, v);
 
-        let mut ok = true;
-        match k.as_str() {
-            "st_tmtheme" => self.load_sublt("st_tmtheme", v.as_str())?,
-            "st_scs" => self.load_sublt("st_scs", v.as_str())?,
-            "st_subltheme" => self.load_sublt("st_subltheme", v.as_str())?,
-            "vscode" => self.load_vscode(v.as_str())?,
-            _ => {
+        let ok = match kv_key(&k) {
+            Some(key) => (key.load)(self, v.as_str())?,
+            None => {
                 warn!("Unrecognized key {}", k);
-                ok = false;
                 false
             }
         };
@@ -130,10 +288,15 @@ impl Theme {
         let key = name.into();
         let mut value = String::new();
         info!("Opening old key file and reading");
-        fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/" + &key)?
-            .read_to_string(&mut value)?;
+        fs::File::open(self.theme_file(&key))?.read_to_string(&mut value)?;
+        if self.executor.record(PlannedAction::Write {
+            path: self.paths.theme_file(&self.name, "theme.json"),
+            overwrite: true,
+        }) {
+            return self.load_k(key, value.trim());
+        }
         info!("Loading current theme store");
-        let mut store = ThemeStore::load(self.name.clone())?;
+        let mut store = ThemeStore::load_in(&self.paths, self.name.clone())?;
         info!("Inserting key and value into key-value store");
         store.kv.insert(
             key.clone(),
@@ -146,194 +309,273 @@ impl Theme {
             .filter(|x| x.as_str() != key.as_str())
             .map(|x| x.to_owned())
             .collect();
-        store.store()?;
+        store.store_in(&self.paths)?;
         info!("Converted option {} to new key-value system", key);
         info!("Loading new key");
-        Ok(self.load_k(key, value)?)
-    }
-    /// Iterates through options and loads them with submethods
-    pub fn load_all(&self) -> Result<()> {
-        use crate::themes::ROption::*;
-        let opt = &self.options;
-        let mut i = 1;
-        let len = opt.len();
-        while i <= len {
-            let ref option = opt[len - i];
-            info!("Loading option {}", option.to_string());
-            match option {
-                Polybar => self.load_poly(self.monitor).unwrap(),
-                OldI3 => self.load_i3(true).unwrap(),
-                I3 => self.load_i3(false).unwrap(),
-                Xres => self.load_xres(false).unwrap(),
-                MergeXRes => self.load_xres(true).unwrap(),
-                Pywal => self.load_pywal().unwrap(),
-                Wall => self.load_wall().unwrap(),
-                Ncmpcpp => {
-                    self.load_ncm().unwrap();
-                }
-                Termite => self.load_termite().unwrap(),
-                Script => self.load_script().unwrap(),
-                Bspwm => self.load_bspwm().unwrap(),
-                Rofi => self.load_rofi().unwrap(),
-                Ranger => self.load_ranger().unwrap(),
-                Lemonbar => self.load_lemon().unwrap(),
-                Openbox => self.load_openbox().unwrap(),
-                Dunst => self.load_dunst().unwrap(),
-                OldTmTheme => {
-                    self.convert_single("st_tmtheme").unwrap();
-                }
-                OldScs => {
-                    self.convert_single("st_scs").unwrap();
-                }
-                OldSublTheme => {
-                    self.convert_single("st_subltheme").unwrap();
-                }
-                VsCode => {
-                    self.convert_single("vscode").unwrap();
-                }
-            };
-            info!("Loaded option {}", option.to_string());
-            i += 1;
+        self.load_k(key, value)
+    }
+    /// Starts a background process for an option, tracking it so the next theme switch stops it
+    pub fn spawn_component(&self, option: &str, spec: CommandSpec) -> Result<()> {
+        let program = spec.program.clone();
+        if let Some(pid) = self.executor.spawn(spec)? {
+            if self.executor.runner().spawns_processes() {
+                track_component(&self.paths, option, program, pid)?;
+            }
         }
-        self.load_kv();
-        info!("Loaded all options for theme {}", self.name);
         Ok(())
     }
-    /// Edits the value of a key in hjson files
-    fn edit_hjson(
-        &self,
-        file: impl Into<String>,
-        pat: impl Into<String>,
-        value: impl Into<String>,
-    ) -> Result<()> {
-        info!("Editing hjson file");
-        let file = &file.into();
-        let pat = &pat.into();
-        let value = &value.into();
-        let mut finals = String::new();
-        if fs::metadata(file).is_ok() {
-            let mut pre = String::new();
-            fs::File::open(file)?.read_to_string(&mut pre)?;
-            let mut patfound = false;
-            for line in pre.lines() {
-                if line.contains(pat) {
-                    patfound = true;
-                    if line.ends_with(",") {
-                        finals = finals + "\n" + "    " + pat + "\"" + &value + "\","
-                    } else {
-                        finals = finals + "\n" + "    " + pat + "\"" + &value + "\""
+    /// Stops the processes an option started, returning whether there were any
+    pub fn unload_components(&self, option: &str) -> Result<bool> {
+        info!("Stopping processes started by option {}", option);
+        Ok(stop_components_in(&self.paths, &self.executor, Some(option))? > 0)
+    }
+    /// Reloads the user's own Xresources, or removes the loaded ones if there aren't any
+    pub fn unload_xres(&self, option: &str) -> Result<()> {
+        let xresources = self.paths.home_file(".Xresources");
+        if fs::metadata(&xresources).is_ok() {
+            info!("Reloading user's own Xresources");
+            self.executor
+                .run(CommandSpec::new("xrdb").arg("-load").arg(xresources))?;
+            return Ok(());
+        }
+        info!("Removing the theme's resources from the loaded Xresources");
+        let theme_keys = xres_keys(&self.read_option(option)?);
+        let loaded = if self.executor.is_dry_run() {
+            String::new()
+        } else {
+            self.executor
+                .runner()
+                .output(&CommandSpec::new("xrdb").arg("-query"))?
+                .stdout
+        };
+        let kept = loaded
+            .lines()
+            .filter(|x| !theme_keys.contains(&xres_key(x)))
+            .map(|x| x.to_string() + "\n")
+            .collect::<String>();
+        let rendered = self.paths.file("rendered/xres_unload");
+        self.executor.create_dir(self.paths.file("rendered"))?;
+        self.executor.write(rendered.as_str(), &kept, 0o644)?;
+        self.executor
+            .run(CommandSpec::new("xrdb").arg("-load").arg(rendered))?;
+        Ok(())
+    }
+    /// Resets VSCode's color theme if it's still the one this theme set. Returns false if it wasn't.
+    pub fn unload_vscode(&self) -> Result<bool> {
+        let value = match self.kv.get("vscode") {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        let mut reset = false;
+        for dir in &["Code/User", "Code - OSS/User"] {
+            let file = self.paths.program(dir) + "/settings.json";
+            let mut st = String::new();
+            if fs::File::open(&file)
+                .and_then(|mut x| x.read_to_string(&mut st))
+                .is_err()
+            {
+                continue;
+            }
+            if get_key(&st, &["workbench.colorTheme"])?.as_ref() == Some(value) {
+                info!("Resetting VSCode color theme in {}", file);
+                let edited = remove_key(&st, &["workbench.colorTheme"])?;
+                self.executor.write(file.as_str(), &edited, 0o666)?;
+                reset = true;
+            }
+        }
+        Ok(reset)
+    }
+    /// Why an option can't be loaded on this system, if the theme's environment is known and lacks its program
+    pub fn missing_reason(&self, option: &str) -> Option<String> {
+        self.environment
+            .as_ref()
+            .and_then(|x| x.missing_reason(option))
+    }
+    /// Iterates through options and loads them with their registered loaders. Failing options don't stop the rest from loading.
+    pub fn load_all(&self) -> Result<ApplyReport> {
+        let mut report = ApplyReport::new(self.name.as_str());
+        // Colors generated by pywal are merged into the palette of this run only, so it goes first
+        let mut theme = self.clone();
+        let mut options: Vec<&String> = self.option_names.iter().rev().collect();
+        options.sort_by_key(|x| *x != "pywal");
+        for option in options {
+            info!("Loading option {}", option);
+            if let Some(reason) = self.missing_reason(option) {
+                warn!("Skipping option {}: {}", option, reason);
+                report.push(option.as_str(), OptionStatus::Skipped(reason));
+                continue;
+            }
+            let status = match self.loaders.get(option) {
+                Some(loader) => match loader.load(&theme) {
+                    Ok(true) => {
+                        info!("Loaded option {}", option);
+                        if option == "pywal" && !self.executor.is_dry_run() {
+                            info!("Merging colors generated by wal into the palette");
+                            match read_pywal(pywal_cache(&self.paths)) {
+                                Ok(colors) => theme.palette.extend(colors),
+                                Err(e) => warn!("Couldn't read the colors generated by wal: {}", e),
+                            }
+                        }
+                        OptionStatus::Applied
+                    }
+                    Ok(false) => {
+                        warn!("Skipped option {}", option);
+                        OptionStatus::Skipped(format!("option {} was not applied", option))
                     }
-                } else if line.ends_with("}") && !patfound {
-                    finals =
-                        finals + "," + "\n" + "    " + pat + "\"" + &value + "\"" + "\n" + line;
-                } else {
-                    finals = finals + "\n" + line;
+                    Err(e) => {
+                        error!("Failed to load option {}: {}", option, e);
+                        OptionStatus::Failed(e)
+                    }
+                },
+                None => {
+                    warn!("No loader registered for option {}", option);
+                    OptionStatus::Skipped(format!("no loader registered for option {}", option))
                 }
-            }
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(file)?
-                .write_all(finals.trim().as_bytes())?
-        } else {
-            finals = finals + "{\n    " + pat + "\"" + &value + "\"\n}";
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(file)?
-                .write_all(finals.as_bytes())?
+            };
+            report.push(option.as_str(), status);
         }
+        report.results.extend(theme.load_kv());
+        info!("Loaded all options for theme {}", self.name);
+        Ok(report)
+    }
+    /// Sets a string value at a path of keys in a JSON settings file, keeping its comments and formatting
+    pub fn edit_json(&self, file: impl Into<String>, path: &[&str], value: &str) -> Result<()> {
+        let file = file.into();
+        if self.executor.record(PlannedAction::EditKey {
+            file: file.clone(),
+            key: path.join("."),
+            value: value.to_string(),
+        }) {
+            return Ok(());
+        }
+        info!("Editing settings file {}", file);
+        let mut pre = String::new();
+        if fs::metadata(&file).is_ok() {
+            fs::File::open(&file)?.read_to_string(&mut pre)?;
+        }
+        let edited = set_key(&pre, path, &json!(value))?;
+        self.executor.write(file.as_str(), &edited, 0o666)?;
         Ok(())
     }
     pub fn load_rofi(&self) -> Result<()> {
-        if fs::metadata(get_home() + "/.config/rofi").is_err() {
-            fs::create_dir(get_home() + "/.config/rofi")?;
-        }
+        self.executor.create_dir(self.paths.program("rofi"))?;
         info!("Copying rofi theme to rofi config");
-        fs::copy(
-            get_home() + "/.config/raven/themes/" + &self.name + "/rofi",
-            get_home() + "/.config/rofi/theme.rasi",
-        )?;
+        self.install_option("rofi", self.paths.program("rofi/theme.rasi"))?;
         Ok(())
     }
+    /// Runs wal on the theme's image. load_all merges the colors it generates into the palette the other options load with.
     pub fn load_pywal(&self) -> Result<()> {
-        let arg = get_home() + "/.config/raven/themes/" + &self.name + "/pywal";
+        let arg = self.theme_file("pywal");
         info!("Starting wal");
-        Command::new("wal").arg("-n").arg("-i").arg(arg).output()?;
+        self.executor
+            .run(CommandSpec::new("wal").arg("-n").arg("-i").arg(arg))?;
         Ok(())
     }
     pub fn load_script(&self) -> Result<()> {
         info!("Starting script");
-        Command::new("sh")
-            .arg("-c")
-            .arg(get_home() + "/.config/raven/themes/" + &self.name + "/script")
-            .output()?;
+        self.executor.run(
+            CommandSpec::new("sh")
+                .arg("-c")
+                .arg(self.option_path("script")?),
+        )?;
         Ok(())
     }
 
+    /// Writes an option file into a program's config. Normally the config is replaced by the option file composed with its base files, but options set to be injected only replace a marked block inside the existing config.
+    fn compose_config(
+        &self,
+        option: &str,
+        target: &str,
+        markers: Markers,
+        mode: u32,
+    ) -> Result<()> {
+        let mut config = String::new();
+        if self.injected.iter().any(|x| x == option) {
+            if fs::metadata(target).is_ok() {
+                info!("Opening and reading existing config {}", target);
+                fs::File::open(target)?.read_to_string(&mut config)?;
+            }
+            info!("Injecting option {} into {}", option, target);
+            config = inject_block(&config, &self.read_option(option)?, &markers)?;
+        } else {
+            info!("Composing {} file", option);
+            config = self.compose_option(option)?;
+        }
+        info!("Writing new {}", target);
+        self.executor.write(target, &config, mode)
+    }
+    /// Removes an injected option's block from a program's config, leaving the rest of it alone. Returns whether there was a block to remove.
+    fn remove_injected(&self, option: &str, target: &str, markers: Markers) -> Result<bool> {
+        if !self.injected.iter().any(|x| x == option) || fs::metadata(target).is_err() {
+            return Ok(false);
+        }
+        let mut config = String::new();
+        fs::File::open(target)?.read_to_string(&mut config)?;
+        let removed = remove_block(&config, &markers)?;
+        if removed == config {
+            return Ok(false);
+        }
+        info!("Removing option {} from {}", option, target);
+        let mode = fs::metadata(target)?.permissions().mode();
+        self.executor.write(target, &removed, mode)?;
+        Ok(true)
+    }
     pub fn load_openbox(&self) -> Result<()> {
-        let mut base = String::new();
-        if fs::metadata(get_home() + "/.config/raven/base_rc.xml").is_ok() {
-            info!("Opening and reading base_rc");
-            fs::File::open(get_home() + "/.config/raven/base_rc.xml")?.read_to_string(&mut base)?;
-        }
-        let mut rest = String::new();
-        info!("Opening and reading openbox config");
-        fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/openbox")?
-            .read_to_string(&mut rest)?;
-        base.push_str(&rest);
-        info!("Removing old openbox config");
-        fs::remove_file(get_home() + "/.config/openbox/rc.xml")?;
-        info!("Creating and writing to new openbox config");
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(get_home() + "/.config/openbox/rc.xml")?
-            .write_all(base.as_bytes())?;
+        self.compose_config(
+            "openbox",
+            &self.paths.program("openbox/rc.xml"),
+            Markers::xml("</openbox_config>"),
+            0o666,
+        )?;
+        self.reload_openbox()
+    }
+    /// Removes an injected openbox config and makes openbox reread it
+    pub fn unload_openbox(&self) -> Result<()> {
+        self.remove_injected(
+            "openbox",
+            &self.paths.program("openbox/rc.xml"),
+            Markers::xml("</openbox_config>"),
+        )?;
+        self.reload_openbox()
+    }
+    /// Makes openbox reread its config
+    pub fn reload_openbox(&self) -> Result<()> {
         info!("Starting openbox reload command");
-        Command::new("openbox").arg("--reconfigure").output()?;
-        Ok(())
+        self.executor
+            .run(CommandSpec::new("openbox").arg("--reconfigure"))
     }
     pub fn load_ranger(&self) -> Result<()> {
         info!("Copying ranger config to ranger directory");
-        fs::copy(
-            get_home() + "/.config/raven/themes/" + &self.name + "/ranger",
-            get_home() + "/.config/ranger/rc.conf",
-        )?;
+        self.install_option("ranger", self.paths.program("ranger/rc.conf"))?;
         Ok(())
     }
 
     pub fn load_dunst(&self) -> Result<()> {
-        let mut config = String::new();
-        if fs::metadata(get_home() + "/.config/raven/base_dunst").is_ok() {
-            info!("Opening and reading base dunst file");
-            fs::File::open(get_home() + "/.config/raven/base_dunst")?
-                .read_to_string(&mut config)?;
-        }
-        let mut app = String::new();
-        info!("Opening and reading dunst file");
-        fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/dunst")?
-            .read_to_string(&mut app)?;
-        config.push_str(&app);
-        info!("Removing old dunstrc");
-        fs::remove_file(get_home() + "/.config/dunst/dunstrc")?;
-        info!("Creating and writing to new dunstrc");
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(get_home() + "/.config/dunst/dunstrc")?
-            .write_all(config.as_bytes())?;
+        self.compose_config(
+            "dunst",
+            &self.paths.program("dunst/dunstrc"),
+            Markers::hash(),
+            0o666,
+        )?;
         info!("Starting dunst");
-        Command::new("dunst").spawn()?;
+        self.spawn_component("dunst", CommandSpec::new("dunst"))?;
         Ok(())
     }
+    /// Stops the dunst this theme started and removes an injected dunst config. Returns false if there was neither.
+    pub fn unload_dunst(&self) -> Result<bool> {
+        let removed = self.remove_injected(
+            "dunst",
+            &self.paths.program("dunst/dunstrc"),
+            Markers::hash(),
+        )?;
+        Ok(self.unload_components("dunst")? || removed)
+    }
     pub fn load_vscode<N>(&self, value: N) -> Result<bool>
     where
         N: Into<String>,
     {
-        let path1 = get_home() + "/.config/Code/User";
-        let path2 = get_home() + "/.config/Code - OSS/User";
+        let path1 = self.paths.program("Code/User");
+        let path2 = self.paths.program("Code - OSS/User");
         if fs::metadata(&path1).is_err() && fs::metadata(&path2).is_err() {
             error!(
                 "Couldn't find neither .config/Code nor .config/Code - OSS. Do you have VSCode installed? \
@@ -341,21 +583,28 @@ impl Theme {
             );
             return Ok(false);
         }
-        let pattern = "\"workbench.colorTheme\": ";
         let value = value.into();
         if fs::metadata(&path1).is_ok() {
             info!("Editing ~/.config/Code/User sublime settings");
-            self.edit_hjson(path1 + "/settings.json", pattern, value.as_str())?;
+            self.edit_json(
+                path1 + "/settings.json",
+                &["workbench.colorTheme"],
+                value.as_str(),
+            )?;
         }
         if fs::metadata(&path2).is_ok() {
             info!("Editing ~/.config/Code - OSS/User sublime settings");
-            self.edit_hjson(path2 + "/settings.json", pattern, value)?;
+            self.edit_json(
+                path2 + "/settings.json",
+                &["workbench.colorTheme"],
+                value.as_str(),
+            )?;
         }
         Ok(true)
     }
     pub fn load_sublt(&self, stype: impl Into<String>, value: impl Into<String>) -> Result<bool> {
         let stype = &stype.into();
-        let path = get_home() + "/.config/sublime-text-3/Packages/User";
+        let path = self.paths.program("sublime-text-3/Packages/User");
         if fs::metadata(&path).is_err() {
             error!(
                 "Couldn't find {}. Do you have sublime text 3 installed? \
@@ -369,36 +618,29 @@ impl Theme {
         if value.starts_with("sublt/") {
             value = value.trim_start_matches("sublt/").to_string();
             info!("Copying file {}", value);
-            fs::copy(
-                get_home() + "/.config/raven/themes/" + &self.name + "/sublt/" + &value,
+            self.install_option(
+                &(String::from("sublt/") + &value),
                 path.clone() + "/" + &value,
             )?;
         }
 
-        let mut pattern = "";
-        if stype == "st_tmtheme" || stype == "st_scs" {
-            pattern = "\"color_scheme\": ";
-        } else if stype == "st_subltheme" {
-            pattern = "\"theme\": ";
-        }
+        let key = if stype == "st_subltheme" {
+            "theme"
+        } else {
+            "color_scheme"
+        };
         info!("Editing sublime preferences");
-        self.edit_hjson(path + "/Preferences.sublime-settings", pattern, value)?;
+        self.edit_json(path + "/Preferences.sublime-settings", &[key], &value)?;
         Ok(true)
     }
 
     pub fn load_ncm(&self) -> Result<bool> {
-        if fs::metadata(get_home() + "/.config/ncmpcpp").is_ok() {
+        if fs::metadata(self.paths.program("ncmpcpp")).is_ok() {
             info!("Copying ncmpcpp config to ~/.config/ncmpcpp");
-            fs::copy(
-                get_home() + "/.config/raven/themes/" + &self.name + "/ncmpcpp",
-                get_home() + "/.config/ncmpcpp/config",
-            )?;
-        } else if fs::metadata(get_home() + "/.ncmpcpp").is_ok() {
+            self.install_option("ncmpcpp", self.paths.program("ncmpcpp/config"))?;
+        } else if fs::metadata(self.paths.home_file(".ncmpcpp")).is_ok() {
             info!("Copying ncmpcpp config to ~/.ncmpcpp");
-            fs::copy(
-                get_home() + "/.config/raven/themes/" + &self.name + "/ncmpcpp",
-                get_home() + "/.ncmpcpp/config",
-            )?;
+            self.install_option("ncmpcpp", self.paths.home_file(".ncmpcpp/config"))?;
         } else {
             error!(
                 "Couldn't detect a ncmpcpp config directory in ~/.config/ncmppcp or ~/.ncmpcpp."
@@ -408,138 +650,180 @@ impl Theme {
         Ok(true)
     }
     pub fn load_bspwm(&self) -> Result<()> {
-        let mut config = String::new();
-        if fs::metadata(get_home() + "/.config/raven/base_bspwm").is_ok() {
-            info!("Opening and reading base bspwm file");
-            fs::File::open(get_home() + "/.config/raven/base_bspwm")?
-                .read_to_string(&mut config)?;
-        }
-        let mut app = String::new();
-        info!("Opening and reading bspwm config");
-        fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/bspwm")?
-            .read_to_string(&mut app)?;
-        config.push_str(&app);
-        info!("Removing old bspwmrc");
-        fs::remove_file(get_home() + "/.config/bspwm/bspwmrc")?;
-        info!("Creating and writing to new bspwmrc");
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .mode(0o744)
-            .open(get_home() + "/.config/bspwm/bspwmrc")?
-            .write_all(config.as_bytes())?;
+        self.compose_config(
+            "bspwm",
+            &self.paths.program("bspwm/bspwmrc"),
+            Markers::hash(),
+            0o744,
+        )?;
         info!("Starting bspwmrc");
-        Command::new("sh")
-            .arg("-c")
-            .arg(get_home() + "/.config/bspwm/bspwmrc")
-            .output()?;
+        self.executor.run(
+            CommandSpec::new("sh")
+                .arg("-c")
+                .arg(self.paths.program("bspwm/bspwmrc")),
+        )?;
         Ok(())
     }
+    /// Removes an injected bspwm config. Returns false if there wasn't one.
+    pub fn unload_bspwm(&self) -> Result<bool> {
+        self.remove_injected(
+            "bspwm",
+            &self.paths.program("bspwm/bspwmrc"),
+            Markers::hash(),
+        )
+    }
     pub fn load_i3(&self, isw: bool) -> Result<()> {
-        let mut config = String::new();
-        if fs::metadata(get_home() + "/.config/raven/base_i3").is_ok() {
-            info!("Opening and reading base i3 config");
-            fs::File::open(get_home() + "/.config/raven/base_i3")?.read_to_string(&mut config)?;
-        }
-        let mut app = String::new();
-        if isw {
-            info!("Loading and reading old-style i3 config");
-            fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/wm")?
-                .read_to_string(&mut app)?;
+        let option = if isw {
+            info!("Loading old-style i3 config");
+            "wm"
         } else {
-            info!("Loading and reading i3 config");
-            fs::File::open(get_home() + "/.config/raven/themes/" + &self.name + "/i3")?
-                .read_to_string(&mut app)?;
-        }
-        config.push_str(&app);
-        if fs::metadata(get_home() + "/.config/i3").is_err() {
-            info!("Creating dir ~/.config/i3");
-            fs::create_dir(get_home() + "/.config/i3")?;
-        }
-        if fs::metadata(get_home() + "/.config/i3/config").is_ok() {
-            info!("Removing old i3 config");
-            fs::remove_file(get_home() + "/.config/i3/config")?;
-        }
-        info!("Creating and writing to i3 config");
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(get_home() + "/.config/i3/config")?
-            .write_all(config.as_bytes())?;
+            info!("Loading i3 config");
+            "i3"
+        };
+        self.executor.create_dir(self.paths.program("i3"))?;
+        self.compose_config(
+            option,
+            &self.paths.program("i3/config"),
+            Markers::hash(),
+            0o666,
+        )?;
+        self.reload_i3()
+    }
+    /// Removes an injected i3 config and makes i3 reread it
+    pub fn unload_i3(&self, isw: bool) -> Result<()> {
+        let option = if isw { "wm" } else { "i3" };
+        self.remove_injected(option, &self.paths.program("i3/config"), Markers::hash())?;
+        self.reload_i3()
+    }
+    /// Makes i3 reread its config
+    pub fn reload_i3(&self) -> Result<()> {
         info!("Starting command to reload i3");
-        Command::new("i3-msg").arg("reload").output()?;
-        Ok(())
+        self.executor.run(CommandSpec::new("i3-msg").arg("reload"))
     }
     pub fn load_termite(&self) -> Result<()> {
         info!("Copying termite config to termite dir");
-        fs::copy(
-            get_home() + "/.config/raven/themes/" + &self.name + "/termite",
-            get_home() + "/.config/termite/config",
-        )?;
+        self.install_option("termite", self.paths.program("termite/config"))?;
+        self.reload_termite()
+    }
+    /// Makes running termites reread their config
+    pub fn reload_termite(&self) -> Result<()> {
         info!("Sending SIGUSR1 to termite processes");
-        Command::new("pkill")
-            .arg("-SIGUSR1")
-            .arg("termite")
-            .output()?;
-        Ok(())
+        self.executor
+            .run(CommandSpec::new("pkill").arg("-SIGUSR1").arg("termite"))
     }
-    pub fn load_poly(&self, monitor: i32) -> Result<()> {
-        for number in 0..monitor {
-            info!("Starting polybar for monitor #{}", number);
-            Command::new("sh")
-                .arg("-c")
-                .arg(
-                    String::from("polybar --config=")
-                        + &get_home()
-                        + "/.config/raven/themes/"
-                        + &self.name
-                        + "/poly "
-                        + &self.order[number as usize]
-                        + " > /dev/null 2> /dev/null",
-                )
-                .spawn()?;
+    /// Starts polybar bars on each connected monitor, skipping bars the theme's polybar config doesn't define. The given count of monitors is only used when none can be detected, and in dry runs. Returns false if no bars could be started.
+    pub fn load_poly(&self, monitor: i32) -> Result<bool> {
+        let processed = self.process_option("poly", true)?;
+        let contents = match processed {
+            Some(ref contents) => contents.clone(),
+            None => fs::read_to_string(self.theme_file("poly"))?,
+        };
+        let config = self.rendered_path("poly", processed)?;
+        let selection = select_bars(&contents, &self.bars, &self.order);
+        for bar in &selection.missing {
+            warn!(
+                "Bar {} isn't defined in the polybar config of theme {}, not starting it",
+                bar, self.name
+            );
         }
-        Ok(())
+        // Running xrandr isn't recorded, so dry runs plan with the configured count
+        let monitors = if self.executor.is_dry_run() {
+            Vec::new()
+        } else {
+            detect_monitors_with(self.executor.runner())
+        };
+        let count = if monitors.is_empty() && !self.executor.is_dry_run() {
+            fallback_count(&self.paths.drm, monitor)
+        } else {
+            monitor
+        };
+        let assigned = assign_bars(&monitors, &selection.layout, &selection.order, count);
+        if assigned.is_empty() {
+            error!("No polybar bars to start for theme {}", self.name);
+            return Ok(false);
+        }
+        for assigned in assigned {
+            let mut spec = CommandSpec::new("polybar");
+            match assigned.monitor {
+                Some(monitor) => {
+                    info!("Starting polybar bar {} on {}", assigned.bar, monitor);
+                    spec = spec.env("MONITOR", monitor);
+                }
+                None => info!("Starting polybar bar {}", assigned.bar),
+            }
+            self.spawn_component(
+                "poly",
+                spec.arg(String::from("--config=") + &config)
+                    .arg(assigned.bar),
+            )?;
+        }
+        Ok(true)
     }
-    fn load_lemon(&self) -> Result<()> {
+    pub fn load_lemon(&self) -> Result<()> {
         info!("Starting lemonbar script");
-        Command::new("sh")
-            .arg(get_home() + "/.config/raven/themes/" + &self.name + "/lemonbar")
-            .spawn()?;
+        self.spawn_component(
+            "lemonbar",
+            CommandSpec::new("sh").arg(self.option_path("lemonbar")?),
+        )?;
         Ok(())
     }
-    fn load_wall(&self) -> Result<()> {
+    pub fn load_wall(&self) -> Result<()> {
         info!("Starting feh to load wallpaper");
-        Command::new("feh")
-            .arg("--bg-scale")
-            .arg(get_home() + "/.config/raven/themes/" + &self.name + "/wall")
-            .output()?;
+        self.executor.run(
+            CommandSpec::new("feh")
+                .arg("--bg-scale")
+                .arg(self.theme_file("wall")),
+        )?;
         Ok(())
     }
-    fn load_xres(&self, merge: bool) -> Result<()> {
-        let mut xres = Command::new("xrdb");
+    pub fn load_xres(&self, merge: bool) -> Result<()> {
+        let mut xres = CommandSpec::new("xrdb");
         let mut name = String::from("xres");
         if merge {
             name.push_str("_m");
-            xres.arg("-merge");
+            xres = xres.arg("-merge");
         }
         info!("Loading xresources file");
-        xres.arg(get_home() + "/.config/raven/themes/" + &self.name + "/" + &name)
-            .output()?;
+        self.executor.run(xres.arg(self.option_path(&name)?))?;
         Ok(())
     }
 }
 
+/// The resource name of an Xresources line, ignoring whitespace
+fn xres_key(line: &str) -> String {
+    line.split(':')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .filter(|x| !x.is_whitespace())
+        .collect()
+}
+/// The resource names set by an Xresources file, skipping comments and preprocessor lines
+fn xres_keys(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| x.contains(':') && !x.starts_with('!') && !x.starts_with('#'))
+        .map(xres_key)
+        .collect()
+}
 /// Changes the theme that is currently being edited
 pub fn edit<N>(theme_name: N) -> Result<String>
+where
+    N: Into<String>,
+{
+    edit_in(&RavenPaths::try_new()?, theme_name)
+}
+/// Changes the theme that is currently being edited in the given paths
+pub fn edit_in<N>(paths: &RavenPaths, theme_name: N) -> Result<String>
 where
     N: Into<String>,
 {
     let theme_name = theme_name.into();
-    if fs::metadata(get_home() + "/.config/raven/themes/" + &theme_name).is_ok() {
-        let mut conf = get_config()?;
+    if fs::metadata(paths.theme(&theme_name)).is_ok() {
+        let mut conf = get_config_in(paths)?;
         conf.editing = theme_name.to_string();
-        up_config(conf)?;
+        up_config_in(paths, conf)?;
         println!("You are now editing the theme {}", &theme_name);
         Ok(theme_name)
     } else {
@@ -549,31 +833,40 @@ where
 }
 /// Clears possible remnants of old themes
 pub fn clear_prev() -> Result<()> {
-    info!("Killing polybar, lemonbar, and dunst");
-    Command::new("pkill").arg("polybar").output()?;
-    Command::new("pkill").arg("lemonbar").output()?;
-    Command::new("pkill").arg("dunst").output()?;
+    clear_prev_with(&RavenPaths::try_new()?, &Executor::new())
+}
+/// Clears possible remnants of old themes in the given paths, using the given executor. Only processes that themes started are stopped.
+pub fn clear_prev_with(paths: &RavenPaths, executor: &Executor) -> Result<()> {
+    info!("Stopping processes started by the previous theme");
+    let stopped = stop_components_in(paths, executor, None)?;
+    info!("Stopped {} processes", stopped);
     Ok(())
 }
 /// Deletes theme from registry
 pub fn del_theme<N>(theme_name: N) -> Result<()>
+where
+    N: Into<String>,
+{
+    del_theme_in(&RavenPaths::try_new()?, theme_name)
+}
+/// Deletes a theme from the themes directory of the given paths
+pub fn del_theme_in<N>(paths: &RavenPaths, theme_name: N) -> Result<()>
 where
     N: Into<String>,
 {
     info!("Removing theme directory");
-    fs::remove_dir_all(get_home() + "/.config/raven/themes/" + &theme_name.into())?;
+    fs::remove_dir_all(paths.theme(&theme_name.into()))?;
     Ok(())
 }
 /// Loads last loaded theme from string of last theme's name
-pub fn refresh_theme<N>(last: N) -> Result<()>
+pub fn refresh_theme<N>(last: N) -> Result<ApplyReport>
 where
     N: Into<String>,
 {
     let last = last.into();
     if last.chars().count() > 0 {
         info!("Running last loaded theme");
-        run_theme(&load_theme(last.trim())?)?;
-        Ok(())
+        run_theme(&load_theme(last.trim())?)
     } else {
         error!("No last theme saved. Cannot refresh.");
         Err(ErrorKind::InvalidThemeName(last).into())
@@ -581,29 +874,47 @@ where
 }
 /// Create new theme directory and 'theme' file
 pub fn new_theme<N>(theme_name: N) -> Result<()>
+where
+    N: Into<String>,
+{
+    new_theme_in(&RavenPaths::try_new()?, theme_name)
+}
+/// Create new theme directory and 'theme' file in the given paths, and start editing it
+pub fn new_theme_in<N>(paths: &RavenPaths, theme_name: N) -> Result<()>
 where
     N: Into<String>,
 {
     let theme_name = theme_name.into();
+    create_theme_in(paths, theme_name.as_str())?;
+    let mut conf = get_config_in(paths)?;
+    conf.editing = theme_name.clone();
+    up_config_in(paths, conf)?;
+    println!("You are now editing the theme {}", &theme_name);
+    Ok(())
+}
+/// Creates an empty theme's directory and store in the given paths, without switching to editing it
+pub fn create_theme_in(paths: &RavenPaths, theme_name: &str) -> Result<()> {
     info!("Creating theme dir");
-    fs::create_dir(get_home() + "/.config/raven/themes/" + &theme_name)?;
+    fs::create_dir(paths.theme(theme_name))?;
     info!("Creating theme store file");
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
-        .open(get_home() + "/.config/raven/themes/" + &theme_name + "/theme.json")?;
+        .open(paths.theme_file(theme_name, "theme.json"))?;
     let stdef = ThemeStore {
-        name: theme_name.clone(),
+        name: theme_name.to_string(),
         options: vec![],
         enabled: vec![],
         screenshot: default_screen(),
         description: default_desc(),
         kv: Map::new(),
+        extends: None,
+        palette: Palette::new(),
+        bars: BarLayout::default(),
     };
     let st = serde_json::to_string(&stdef)?;
     info!("Writing to theme store");
     file.write_all(st.as_bytes())?;
-    edit(theme_name)?;
     Ok(())
 }
 /// Add an option to a theme
@@ -611,20 +922,19 @@ pub fn add_to_theme(
     theme_name: impl Into<String>,
     option: impl Into<String>,
     path: impl Into<String>,
+) -> Result<()> {
+    add_to_theme_in(&RavenPaths::try_new()?, theme_name, option, path)
+}
+/// Add an option to a theme in the given paths, copying it from a path relative to the current directory
+pub fn add_to_theme_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    option: impl Into<String>,
+    path: impl Into<String>,
 ) -> Result<()> {
     let (theme_name, option, path) = (theme_name.into(), option.into(), path.into());
-    info!("Loading theme");
-    let cur_theme = load_theme(theme_name.as_str())?;
-    let cur_st = ThemeStore::load(theme_name.as_str())?;
-    let opts = cur_theme.options.iter().map(|x| x.to_string()).collect();
-    let mut new_themes = ThemeStore {
-        name: theme_name.clone(),
-        options: opts,
-        enabled: cur_theme.enabled,
-        screenshot: cur_st.screenshot,
-        description: cur_st.description,
-        kv: Map::new(),
-    };
+    info!("Loading theme store");
+    let mut new_themes = ThemeStore::load_in(paths, theme_name.as_str())?;
     let mut already_used = false;
     for opt in &new_themes.options {
         if opt == &option {
@@ -634,33 +944,27 @@ pub fn add_to_theme(
     if !already_used {
         info!("Adding new option to theme. Updating theme store.");
         new_themes.options.push(option.clone());
-        new_themes.store()?;
+        new_themes.store_in(paths)?;
     }
     let mut totpath = env::current_dir()?;
     totpath.push(path);
     info!("Copying option {} to theme directory", option);
-    fs::copy(
-        totpath,
-        get_home() + "/.config/raven/themes/" + &theme_name + "/" + &option,
-    )?;
+    fs::copy(totpath, paths.theme_file(&theme_name, &option))?;
     Ok(())
 }
 /// Remove an option from a theme
 pub fn rm_from_theme(theme_name: impl Into<String>, option: impl Into<String>) -> Result<()> {
+    rm_from_theme_in(&RavenPaths::try_new()?, theme_name, option)
+}
+/// Remove an option from a theme stored under the given paths
+pub fn rm_from_theme_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    option: impl Into<String>,
+) -> Result<()> {
     let (theme_name, option) = (theme_name.into(), option.into());
-    info!("Loading theme");
-    let cur_theme = load_theme(theme_name.as_str())?;
     info!("Loading store");
-    let cur_st = ThemeStore::load(theme_name.as_str())?;
-    let opts = cur_theme.options.iter().map(|x| x.to_string()).collect();
-    let mut new_themes = ThemeStore {
-        name: theme_name.clone(),
-        options: opts,
-        enabled: cur_theme.enabled,
-        screenshot: cur_st.screenshot,
-        description: cur_st.description,
-        kv: Map::new(),
-    };
+    let mut new_themes = ThemeStore::load_in(paths, theme_name.as_str())?;
     let mut found = false;
     let mut i = 0;
     while i < new_themes.options.len() {
@@ -673,28 +977,159 @@ pub fn rm_from_theme(theme_name: impl Into<String>, option: impl Into<String>) -
     }
     if found {
         info!("Updating theme store.");
-        new_themes.store()?;
+        new_themes.store_in(paths)?;
         Ok(())
     } else {
         error!("Couldn't find option {}", option);
         Err(ErrorKind::InvalidThemeName(theme_name).into())
     }
 }
-/// Run/refresh a loaded Theme
-pub fn run_theme(new_theme: &Theme) -> Result<()> {
-    clear_prev()?;
+/// Run/refresh a loaded Theme, returning what happened to each of its options
+pub fn run_theme(new_theme: &Theme) -> Result<ApplyReport> {
+    clear_prev_with(&new_theme.paths, &new_theme.executor)?;
+    let mut conf = get_config_in(&new_theme.paths)?;
+    let mut theme = new_theme.clone();
+    if !theme.executor.is_dry_run() {
+        if let Some(set) = BackupSet::new(&theme.paths, theme.name.as_str(), conf.backups) {
+            info!("Backing up files changed by theme");
+            theme.executor = theme.executor.with_backup(set);
+        }
+    }
+    if theme.environment.is_none() {
+        theme.environment = Some(detect_in(&theme.paths, &theme.loaders));
+    }
     info!("Running theme options");
-    new_theme.load_all()?;
+    let report = theme.load_all()?;
     // Updates the 'last loaded theme' information for later use by raven refresh
-    let mut conf = get_config()?;
-    conf.last = new_theme.name.clone();
-    up_config(conf)?;
-    Ok(())
+    if !theme.executor.is_dry_run() {
+        conf.last = new_theme.name.clone();
+        up_config_in(&new_theme.paths, conf)?;
+    }
+    Ok(report)
+}
+/// Unloads the last run theme, returning the desktop to how it was before raven changed it
+pub fn unload_theme() -> Result<ApplyReport> {
+    unload_theme_in(
+        &RavenPaths::try_new()?,
+        &Executor::new(),
+        &LoaderRegistry::default(),
+        None,
+    )
+}
+/// Unloads the last theme run in the given paths through an executor, with the given loaders and environment. Without an environment, it's detected. The backup sets made by runs of that theme since it was applied are restored, then each option's unload step runs, and finally all remaining processes started by themes are stopped.
+pub fn unload_theme_in(
+    paths: &RavenPaths,
+    executor: &Executor,
+    loaders: &LoaderRegistry,
+    environment: Option<Environment>,
+) -> Result<ApplyReport> {
+    let mut conf = get_config_in(paths)?;
+    let last = conf.last.clone();
+    let sets = list_backups_in(paths)?
+        .iter()
+        .rev()
+        .take_while(|x| x.theme == last)
+        .count();
+    let restored = rollback_with(paths, executor, sets)?;
+    info!("Restored {} backup sets", restored);
+    let mut report = ApplyReport::new(last.as_str());
+    if !last.is_empty() {
+        let mut theme = load_theme_in(paths, last.as_str())?;
+        theme.executor = executor.clone();
+        theme.loaders = loaders.clone();
+        theme.environment = Some(environment.unwrap_or_else(|| detect_in(paths, loaders)));
+        for option in theme.option_names.iter().chain(theme.kv.keys()) {
+            info!("Unloading option {}", option);
+            if let Some(reason) = theme.missing_reason(option) {
+                report.push(option.as_str(), OptionStatus::Skipped(reason));
+                continue;
+            }
+            let status = match theme.loaders.get(option).map(|x| x.unload(&theme)) {
+                Some(Ok(true)) => OptionStatus::Applied,
+                Some(Ok(false)) | None => {
+                    OptionStatus::Skipped(format!("option {} had nothing to unload", option))
+                }
+                Some(Err(e)) => {
+                    error!("Failed to unload option {}: {}", option, e);
+                    OptionStatus::Failed(e)
+                }
+            };
+            report.push(option.as_str(), status);
+        }
+    }
+    clear_prev_with(paths, executor)?;
+    if !executor.is_dry_run() {
+        conf.last = String::new();
+        up_config_in(paths, conf)?;
+    }
+    Ok(report)
+}
+/// Describes everything running a theme would do, without doing any of it. The report tells which options would be skipped or can't be planned.
+pub fn plan_theme(theme: &Theme) -> Result<(Vec<PlannedAction>, ApplyReport)> {
+    let mut dry = theme.clone();
+    dry.executor = Executor::dry_run();
+    if dry.environment.is_none() {
+        dry.environment = Some(detect_in(&dry.paths, &dry.loaders));
+    }
+    clear_prev_with(&dry.paths, &dry.executor)?;
+    info!("Planning theme options");
+    let report = dry.load_all()?;
+    for result in report.failed() {
+        warn!(
+            "Option {} can't be planned: {:?}",
+            result.option, result.status
+        );
+    }
+    Ok((dry.executor.planned(), report))
+}
+/// Stores the colors from a pywal colors.json in a theme's palette, defaulting to the colors of pywal's last run. Returns the new palette.
+pub fn import_pywal_palette(
+    theme_name: impl Into<String>,
+    colors: Option<&str>,
+) -> Result<Palette> {
+    import_pywal_palette_in(&RavenPaths::try_new()?, theme_name, colors)
+}
+/// Stores the colors from a pywal colors.json in the palette of a theme in the given paths. Colors pywal doesn't generate are kept.
+pub fn import_pywal_palette_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    colors: Option<&str>,
+) -> Result<Palette> {
+    let colors = colors.map(|x| x.to_string()).unwrap_or(pywal_cache(paths));
+    let imported = read_pywal(colors)?;
+    let mut store = ThemeStore::load_in(paths, theme_name)?;
+    info!("Merging {} pywal colors into palette", imported.len());
+    store.palette.extend(imported);
+    Ok(store.store_in(paths)?.palette)
+}
+/// Generates a palette from a theme's wall file and stores it in the theme's palette. Returns the new palette.
+pub fn extract_wall_palette(
+    theme_name: impl Into<String>,
+    options: &ExtractOptions,
+) -> Result<Palette> {
+    extract_wall_palette_in(&RavenPaths::try_new()?, theme_name, options)
+}
+/// Generates a palette from the wall file of a theme in the given paths and stores it in the theme's palette
+pub fn extract_wall_palette_in(
+    paths: &RavenPaths,
+    theme_name: impl Into<String>,
+    options: &ExtractOptions,
+) -> Result<Palette> {
+    let theme = load_theme_in(paths, theme_name)?;
+    let extracted = extract_palette(theme.theme_file("wall"), options)?;
+    let mut store = ThemeStore::load_in(paths, theme.name.as_str())?;
+    info!("Merging {} extracted colors into palette", extracted.len());
+    store.palette.extend(extracted);
+    Ok(store.store_in(paths)?.palette)
 }
 /// Get all themes
 pub fn get_themes() -> Result<Vec<String>> {
+    get_themes_in(&RavenPaths::try_new()?)
+}
+/// Get all themes in the themes directory of the given paths
+pub fn get_themes_in(paths: &RavenPaths) -> Result<Vec<String>> {
     info!("Reading in all themes");
-    Ok(fs::read_dir(get_home() + "/.config/raven/themes")?
+    Ok(fs::read_dir(paths.themes())?
         .collect::<Vec<io::Result<DirEntry>>>()
         .into_iter()
         .map(|x| proc_path(x.unwrap()))
@@ -706,29 +1141,95 @@ pub fn key_value(
     value: impl Into<String>,
     theme: impl Into<String>,
 ) -> Result<()> {
-    let mut store = ThemeStore::load(theme)?;
+    key_value_in(&RavenPaths::try_new()?, key, value, theme)
+}
+/// Insert a key-value pair into a theme stored under the given paths
+pub fn key_value_in(
+    paths: &RavenPaths,
+    key: impl Into<String>,
+    value: impl Into<String>,
+    theme: impl Into<String>,
+) -> Result<()> {
+    let mut store = ThemeStore::load_in(paths, theme)?;
     info!("Inserting new key-value into store");
     store
         .kv
         .insert(key.into(), serde_json::Value::String(value.into()));
-    store.store()?;
+    store.store_in(paths)?;
     Ok(())
 }
+/// Loads the stores of a theme and every theme it extends, the theme itself first
+pub fn theme_chain_in(paths: &RavenPaths, theme_name: &str) -> Result<Vec<ThemeStore>> {
+    let mut names: Vec<String> = Vec::new();
+    let mut chain = Vec::new();
+    let mut next = Some(theme_name.to_string());
+    while let Some(name) = next {
+        if names.contains(&name) {
+            names.push(name);
+            error!("Theme extends itself: {}", names.join(" -> "));
+            return Err(ErrorKind::ThemeCycle(names.join(" -> ")).into());
+        }
+        if fs::metadata(paths.theme_file(&name, "theme.json")).is_err() {
+            error!("Theme {} does not exist", name);
+            return Err(ErrorKind::InvalidThemeName(name).into());
+        }
+        info!("Loading theme store {} of extends chain", name);
+        let store = ThemeStore::load_in(paths, name.as_str())?;
+        next = store.extends.clone();
+        names.push(name);
+        chain.push(store);
+    }
+    Ok(chain)
+}
+/// Merges a chain of theme stores into a single store that doesn't extend anything. Earlier stores take precedence.
+pub fn flatten_chain(chain: Vec<ThemeStore>) -> ThemeStore {
+    let mut chain = chain.into_iter();
+    let mut flat = chain.next().expect("Can't flatten an empty chain");
+    for parent in chain {
+        for option in parent.options {
+            if !flat.options.contains(&option) {
+                flat.options.push(option);
+            }
+        }
+        for (k, v) in parent.kv {
+            if !flat.kv.contains_key(&k) {
+                flat.kv.insert(k, v);
+            }
+        }
+        for (k, v) in parent.palette {
+            flat.palette.entry(k).or_insert(v);
+        }
+        if flat.bars.is_empty() {
+            flat.bars = parent.bars;
+        }
+    }
+    flat.extends = None;
+    flat
+}
 /// Load in data for a specific theme
 pub fn load_theme<N>(theme_name: N) -> Result<Theme>
+where
+    N: Into<String>,
+{
+    load_theme_in(&RavenPaths::try_new()?, theme_name)
+}
+/// Load in data for a specific theme from the themes directory of the given paths
+pub fn load_theme_in<N>(paths: &RavenPaths, theme_name: N) -> Result<Theme>
 where
     N: Into<String>,
 {
     let theme_name = theme_name.into();
     info!("Loading config");
-    let conf = get_config()?;
+    let conf = get_config_in(paths)?;
     info!("Loading theme directory");
-    let ent_res = fs::read_dir(get_home() + "/.config/raven/themes/" + &theme_name);
+    let ent_res = fs::read_dir(paths.theme(&theme_name));
     if ent_res.is_ok() {
         info!("Found theme {}", theme_name);
-        if fs::metadata(get_home() + "/.config/raven/themes/" + &theme_name + "/theme.json").is_ok()
-        {
-            let theme_info = ThemeStore::load(theme_name.as_str())?;
+        if fs::metadata(paths.theme_file(&theme_name, "theme.json")).is_ok() {
+            let stores = theme_chain_in(paths, theme_name.as_str())?;
+            let mut chain = vec![theme_name.clone()];
+            chain.extend(stores.iter().filter_map(|x| x.extends.clone()));
+            let theme_info = flatten_chain(stores);
             info!("Loading options");
             let opts: Vec<ROption> = theme_info
                 .options
@@ -742,12 +1243,22 @@ where
             let new_theme = Theme {
                 name: theme_name,
                 options: opts,
+                option_names: theme_info.options,
                 monitor: conf.monitors,
                 enabled: theme_info.enabled,
                 order: conf.polybar,
+                injected: conf.injected,
+                bases: conf.bases,
                 kv: theme_info.kv,
                 screenshot: theme_info.screenshot,
                 description: theme_info.description,
+                palette: theme_info.palette,
+                bars: theme_info.bars,
+                paths: paths.clone(),
+                chain,
+                loaders: LoaderRegistry::default(),
+                executor: Executor::new(),
+                environment: None,
             };
             Ok(new_theme)
         } else {
@@ -761,11 +1272,332 @@ where
 }
 /// Loads all themes
 pub fn load_themes() -> Result<Vec<Theme>> {
+    load_themes_in(&RavenPaths::try_new()?)
+}
+/// Loads all themes stored under the given paths
+pub fn load_themes_in(paths: &RavenPaths) -> Result<Vec<Theme>> {
     info!("Loading all themes");
-    Ok(get_themes()?
+    Ok(get_themes_in(paths)?
         .iter()
-        .map(|x| load_theme(x.as_str()))
+        .map(|x| load_theme_in(paths, x.as_str()))
         .filter(|x| x.is_ok())
         .map(|x| x.unwrap())
         .collect::<Vec<Theme>>())
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loaders::OptionLoader;
+    use palette::Color;
+    use runner::RecordingRunner;
+    use std::sync::{Arc, Mutex};
+    /// Creates an empty raven setup inside a home in the temp directory
+    fn test_paths(name: &str) -> RavenPaths {
+        let root = env::temp_dir().join(format!("ravenlib-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let paths = RavenPaths::with_home(root.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        paths
+    }
+    #[test]
+    fn applying_theme_merges_xresources() {
+        let paths = test_paths("apply");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(
+            paths.theme_file("night", "xres_m"),
+            "*.foreground: #ffffff\n",
+        )
+        .unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options.push(String::from("xres_m"));
+        store.store_in(&paths).unwrap();
+        let runner = RecordingRunner::new();
+        let mut theme = load_theme_in(&paths, "night").unwrap();
+        theme.executor = Executor::new().with_runner(runner.clone());
+        theme.environment = Some(Environment::default());
+        let report = run_theme(&theme).unwrap();
+        assert!(report.is_ok());
+        assert!(runner.ran(
+            "xrdb",
+            &["-merge", paths.theme_file("night", "xres_m").as_str()]
+        ));
+        assert_eq!(get_config_in(&paths).unwrap().last, "night");
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn planning_theme_reports_skipped_options() {
+        let paths = test_paths("plan");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(paths.theme_file("night", "xres"), "*.foreground: #ffffff\n").unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = vec![String::from("xres"), String::from("nothing")];
+        store.store_in(&paths).unwrap();
+        let mut theme = load_theme_in(&paths, "night").unwrap();
+        theme.environment = Some(Environment::default());
+        let (planned, report) = plan_theme(&theme).unwrap();
+        assert!(planned.contains(&PlannedAction::Run(
+            CommandSpec::new("xrdb").arg(paths.theme_file("night", "xres"))
+        )));
+        assert!(report.skipped().iter().any(|x| x.option == "nothing"));
+        assert_eq!(get_config_in(&paths).unwrap().last, "");
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    /// Loader that only notes whether it was unloaded
+    struct Marker(Arc<Mutex<bool>>);
+    impl OptionLoader for Marker {
+        fn name(&self) -> &str {
+            "marker"
+        }
+        fn load(&self, _theme: &Theme) -> Result<bool> {
+            Ok(true)
+        }
+        fn unload(&self, _theme: &Theme) -> Result<bool> {
+            *self.0.lock().unwrap() = true;
+            Ok(true)
+        }
+    }
+    #[test]
+    fn unloading_removes_only_the_themes_xresources() {
+        let paths = test_paths("unload");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(
+            paths.theme_file("night", "xres_m"),
+            "! colors\n*.foreground: #ffffff\n",
+        )
+        .unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = vec![String::from("xres_m"), String::from("marker")];
+        store.store_in(&paths).unwrap();
+        let mut conf = get_config_in(&paths).unwrap();
+        conf.last = String::from("night");
+        up_config_in(&paths, conf).unwrap();
+        let unloaded = Arc::new(Mutex::new(false));
+        let mut loaders = LoaderRegistry::default();
+        loaders.register(Marker(unloaded.clone()));
+        let runner =
+            RecordingRunner::new().with_output("xrdb", "*.foreground:\t#ffffff\n*.font:\tmono\n");
+        let executor = Executor::new().with_runner(runner.clone());
+        let report =
+            unload_theme_in(&paths, &executor, &loaders, Some(Environment::default())).unwrap();
+        assert_eq!(report.applied().len(), 2);
+        assert!(*unloaded.lock().unwrap());
+        let rendered = paths.file("rendered/xres_unload");
+        assert!(runner.ran("xrdb", &["-load", rendered.as_str()]));
+        assert!(!runner.ran("xrdb", &["-remove"]));
+        assert_eq!(fs::read_to_string(&rendered).unwrap(), "*.font:\tmono\n");
+        assert_eq!(get_config_in(&paths).unwrap().last, "");
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn unloading_reloads_users_xresources() {
+        let paths = test_paths("unload-base");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(paths.theme_file("night", "xres"), "*.foreground: #ffffff\n").unwrap();
+        fs::write(paths.home_file(".Xresources"), "*.font: mono\n").unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = vec![String::from("xres")];
+        store.store_in(&paths).unwrap();
+        let mut conf = get_config_in(&paths).unwrap();
+        conf.last = String::from("night");
+        up_config_in(&paths, conf).unwrap();
+        let runner = RecordingRunner::new();
+        let executor = Executor::new().with_runner(runner.clone());
+        unload_theme_in(
+            &paths,
+            &executor,
+            &LoaderRegistry::default(),
+            Some(Environment::default()),
+        )
+        .unwrap();
+        let xresources = paths.home_file(".Xresources");
+        assert!(runner.ran("xrdb", &["-load", xresources.as_str()]));
+        assert!(!runner.ran("xrdb", &["-query"]));
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn polybar_falls_back_to_drm_outputs() {
+        let mut paths = test_paths("drm");
+        for (output, status) in &[
+            ("card0-HDMI-A-1", "connected"),
+            ("card0-DP-1", "connected"),
+            ("card0-DP-2", "disconnected"),
+        ] {
+            let dir = paths.home_file("drm/") + output;
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir + "/status", status.to_string() + "\n").unwrap();
+        }
+        paths.drm = paths.home_file("drm");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(
+            paths.theme_file("night", "poly"),
+            "[bar/main]\n[bar/other]\n",
+        )
+        .unwrap();
+        let mut theme = load_theme_in(&paths, "night").unwrap();
+        let runner = RecordingRunner::new();
+        theme.executor = Executor::new().with_runner(runner.clone());
+        assert!(theme.load_poly(1).unwrap());
+        let config = String::from("--config=") + &paths.theme_file("night", "poly");
+        assert!(runner.ran("polybar", &[config.as_str(), "main"]));
+        assert!(runner.ran("polybar", &[config.as_str(), "other"]));
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn installing_renders_only_text_with_placeholders() {
+        let paths = test_paths("render");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(paths.theme_file("night", "rofi"), "* { fg: {{accent}}; }\n").unwrap();
+        fs::write(paths.theme_file("night", "sublt"), [0xff, 0xfe, b'{', b'{']).unwrap();
+        let mut theme = load_theme_in(&paths, "night").unwrap();
+        theme
+            .palette
+            .insert(String::from("accent"), Color::new(0, 0, 0));
+        theme
+            .install_option("rofi", paths.home_file("theme.rasi"))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(paths.home_file("theme.rasi")).unwrap(),
+            "* { fg: #000000; }\n"
+        );
+        theme
+            .install_option("sublt", paths.home_file("sublt"))
+            .unwrap();
+        assert_eq!(
+            fs::read(paths.home_file("sublt")).unwrap(),
+            vec![0xff, 0xfe, b'{', b'{']
+        );
+        assert_eq!(
+            theme.option_path("sublt").unwrap(),
+            paths.theme_file("night", "sublt")
+        );
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn options_are_composed_with_base_files() {
+        let paths = test_paths("compose");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(paths.file("base_i3"), "set $mod Mod4\n").unwrap();
+        fs::write(paths.file("base_poly"), "[settings]\n").unwrap();
+        fs::write(paths.theme_file("night", "wm"), "bar {}\n").unwrap();
+        fs::write(paths.theme_file("night", "poly"), "[bar/main]\n").unwrap();
+        let theme = load_theme_in(&paths, "night").unwrap();
+        assert_eq!(
+            theme.compose_option("wm").unwrap(),
+            "set $mod Mod4\nbar {}\n"
+        );
+        assert_eq!(
+            theme.option_path("poly").unwrap(),
+            paths.theme_file("night", "poly")
+        );
+        let mut conf = get_config_in(&paths).unwrap();
+        conf.bases.insert(
+            String::from("poly"),
+            vec![BaseSpec::new("base_poly", Placement::Prepend)],
+        );
+        up_config_in(&paths, conf).unwrap();
+        let theme = load_theme_in(&paths, "night").unwrap();
+        assert_eq!(
+            theme.option_path("poly").unwrap(),
+            paths.file("rendered/poly")
+        );
+        assert_eq!(
+            fs::read_to_string(paths.file("rendered/poly")).unwrap(),
+            "[settings]\n[bar/main]\n"
+        );
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    /// Stores a theme with the given options, kv and parent
+    fn store_theme(
+        paths: &RavenPaths,
+        name: &str,
+        options: &[&str],
+        kv: &[(&str, &str)],
+        extends: Option<&str>,
+    ) {
+        if fs::metadata(paths.theme(name)).is_err() {
+            new_theme_in(paths, name).unwrap();
+        }
+        let mut store = ThemeStore::load_in(paths, name).unwrap();
+        store.options = options.iter().map(|x| x.to_string()).collect();
+        for (k, v) in kv {
+            store.kv.insert(k.to_string(), Value::String(v.to_string()));
+        }
+        store.extends = extends.map(|x| x.to_string());
+        store.store_in(paths).unwrap();
+    }
+    #[test]
+    fn themes_inherit_from_their_parents() {
+        let paths = test_paths("extends");
+        store_theme(
+            &paths,
+            "base",
+            &["xres"],
+            &[("vscode", "Dark"), ("st_scs", "a")],
+            None,
+        );
+        store_theme(
+            &paths,
+            "night",
+            &["rofi"],
+            &[("vscode", "Light")],
+            Some("base"),
+        );
+        fs::write(paths.theme_file("base", "xres"), "").unwrap();
+        let chain = theme_chain_in(&paths, "night").unwrap();
+        assert_eq!(
+            chain.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+            vec!["night", "base"]
+        );
+        let flat = flatten_chain(chain);
+        assert_eq!(flat.options, vec!["rofi", "xres"]);
+        assert_eq!(flat.kv["vscode"], Value::String(String::from("Light")));
+        assert_eq!(flat.kv["st_scs"], Value::String(String::from("a")));
+        assert_eq!(flat.extends, None);
+        let theme = load_theme_in(&paths, "night").unwrap();
+        assert_eq!(theme.theme_file("xres"), paths.theme_file("base", "xres"));
+        store_theme(&paths, "base", &["xres"], &[], Some("night"));
+        match theme_chain_in(&paths, "night") {
+            Err(Error(ErrorKind::ThemeCycle(cycle), _)) => {
+                assert_eq!(cycle, "night -> base -> night")
+            }
+            _ => panic!("extends cycle wasn't detected"),
+        }
+        store_theme(&paths, "night", &["rofi"], &[], Some("missing"));
+        match theme_chain_in(&paths, "night") {
+            Err(Error(ErrorKind::InvalidThemeName(name), _)) => assert_eq!(name, "missing"),
+            _ => panic!("missing parent wasn't detected"),
+        }
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn injected_options_keep_the_users_config() {
+        let paths = test_paths("inject");
+        new_theme_in(&paths, "night").unwrap();
+        fs::write(
+            paths.theme_file("night", "dunst"),
+            "[global]\nfont = mono\n",
+        )
+        .unwrap();
+        let dunstrc = paths.program("dunst/dunstrc");
+        fs::create_dir_all(paths.program("dunst")).unwrap();
+        fs::write(&dunstrc, "[urgency_low]\ntimeout = 5\n").unwrap();
+        let mut conf = get_config_in(&paths).unwrap();
+        conf.injected = vec![String::from("dunst")];
+        up_config_in(&paths, conf).unwrap();
+        let mut theme = load_theme_in(&paths, "night").unwrap();
+        let runner = RecordingRunner::new();
+        theme.executor = Executor::new().with_runner(runner.clone());
+        theme.load_dunst().unwrap();
+        assert_eq!(
+            fs::read_to_string(&dunstrc).unwrap(),
+            "[urgency_low]\ntimeout = 5\n# >>> raven >>>\n[global]\nfont = mono\n# <<< raven <<<\n"
+        );
+        assert!(runner.ran("dunst", &[]));
+        assert!(theme.unload_dunst().unwrap());
+        assert_eq!(
+            fs::read_to_string(&dunstrc).unwrap(),
+            "[urgency_low]\ntimeout = 5\n"
+        );
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use contrast::check_theme_contrast;
+use error::*;
+use loaders::{kv_key, LoaderRegistry};
+use monitors::select_bars;
+use paths::RavenPaths;
+use proc_path;
+use serde_json::value::Value;
+use std::{fs, io::Read};
+use template::unknown_placeholders;
+use themes::{flatten_chain, load_theme_in, theme_chain_in, ThemeStore};
+/// How serious a problem with a theme is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Severity {
+    /// The theme won't load correctly
+    Error,
+    /// The theme loads, but probably not as intended
+    Warning,
+}
+/// A single problem found in a theme
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The option, kv key or file the problem concerns, if any
+    pub subject: Option<String>,
+    pub message: String,
+}
+impl Diagnostic {
+    /// Creates an error about the given subject
+    pub fn error(subject: Option<&str>, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            subject: subject.map(|x| x.to_string()),
+            message: message.into(),
+        }
+    }
+    /// Creates a warning about the given subject
+    pub fn warning(subject: Option<&str>, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            subject: subject.map(|x| x.to_string()),
+            message: message.into(),
+        }
+    }
+}
+/// Checks a theme's directory against its theme.json
+pub fn validate_theme(theme_name: impl Into<String>) -> Result<Vec<Diagnostic>> {
+    validate_theme_in(
+        &RavenPaths::try_new()?,
+        &LoaderRegistry::default(),
+        theme_name,
+    )
+}
+/// Checks a theme in the given paths against its theme.json, resolving options with the given loaders
+pub fn validate_theme_in(
+    paths: &RavenPaths,
+    loaders: &LoaderRegistry,
+    theme_name: impl Into<String>,
+) -> Result<Vec<Diagnostic>> {
+    let theme_name = theme_name.into();
+    if fs::metadata(paths.theme(&theme_name)).is_err() {
+        error!("Theme does not exist");
+        return Err(ErrorKind::InvalidThemeName(theme_name).into());
+    }
+    info!("Validating theme {}", theme_name);
+    let mut diags = Vec::new();
+    let mut st = String::new();
+    if fs::File::open(paths.theme_file(&theme_name, "theme.json"))
+        .and_then(|mut x| x.read_to_string(&mut st))
+        .is_err()
+    {
+        diags.push(Diagnostic::error(
+            Some("theme.json"),
+            "theme.json is missing or unreadable",
+        ));
+        return Ok(diags);
+    }
+    let store: ThemeStore = match serde_json::from_str(&st) {
+        Ok(store) => store,
+        Err(e) => {
+            diags.push(Diagnostic::error(
+                Some("theme.json"),
+                format!("theme.json can't be parsed: {}", e),
+            ));
+            return Ok(diags);
+        }
+    };
+    if store.name != theme_name {
+        diags.push(Diagnostic::warning(
+            Some("theme.json"),
+            format!(
+                "theme.json names the theme {}, but its directory is {}",
+                store.name, theme_name
+            ),
+        ));
+    }
+    let mut dirs = vec![theme_name.clone()];
+    let store = match store.extends.clone() {
+        Some(_) => match theme_chain_in(paths, &theme_name) {
+            Ok(stores) => {
+                dirs.extend(stores.iter().filter_map(|x| x.extends.clone()));
+                flatten_chain(stores)
+            }
+            Err(e) => {
+                diags.push(Diagnostic::error(
+                    Some("extends"),
+                    format!("extends chain can't be resolved: {}", e),
+                ));
+                store
+            }
+        },
+        None => store,
+    };
+    let exists = |file: &str| {
+        dirs.iter()
+            .any(|x| fs::metadata(paths.theme_file(x, file)).is_ok())
+    };
+    let mut used = vec![
+        String::from("theme.json"),
+        String::from("~theme.json"),
+        String::from("sublt"),
+    ];
+    for option in &store.options {
+        match loaders.get(option) {
+            Some(loader) => {
+                for file in loader.files() {
+                    if !exists(&file) {
+                        diags.push(Diagnostic::error(
+                            Some(option),
+                            format!("option {} needs the missing file {}", option, file),
+                        ));
+                    }
+                    used.push(file);
+                }
+            }
+            None => diags.push(Diagnostic::warning(
+                Some(option),
+                format!("unknown option {} will be ignored", option),
+            )),
+        }
+    }
+    for entry in fs::read_dir(paths.theme(&theme_name))? {
+        let file = proc_path(entry?);
+        if used.contains(&file) {
+            continue;
+        }
+        if loaders.get(&file).is_some() {
+            diags.push(Diagnostic::warning(
+                Some(&file),
+                format!("file {} isn't listed in the theme's options", file),
+            ));
+        } else {
+            diags.push(Diagnostic::warning(
+                Some(&file),
+                format!("file {} isn't used by any option", file),
+            ));
+        }
+    }
+    for (k, v) in &store.kv {
+        if kv_key(k).is_none() {
+            diags.push(Diagnostic::warning(
+                Some(k),
+                format!("unrecognized key {} will be ignored", k),
+            ));
+        }
+        match *v {
+            Value::String(ref v) => {
+                if k.starts_with("st_") && v.starts_with("sublt/") && !exists(v) {
+                    diags.push(Diagnostic::error(
+                        Some(k),
+                        format!("key {} references the missing file {}", k, v),
+                    ));
+                }
+            }
+            _ => diags.push(Diagnostic::error(
+                Some(k),
+                format!("value of key {} is not a string", k),
+            )),
+        }
+    }
+    let theme = match load_theme_in(paths, theme_name.as_str()) {
+        Ok(theme) => theme,
+        Err(e) => {
+            warn!(
+                "Couldn't load theme {} to check its colors and bars: {}",
+                theme_name, e
+            );
+            info!("Found {} problems in theme {}", diags.len(), theme_name);
+            return Ok(diags);
+        }
+    };
+    info!("Checking color contrast");
+    for option in &theme.option_names {
+        if let Err(e) = theme.read_option(option) {
+            if let ErrorKind::TemplateError(_) = *e.kind() {
+                diags.push(Diagnostic::error(
+                    Some(option),
+                    format!("option {} can't be rendered: {}", option, e),
+                ));
+            }
+        }
+        if theme.palette.is_empty() {
+            continue;
+        }
+        if let Ok(contents) = fs::read_to_string(theme.theme_file(option)) {
+            for name in unknown_placeholders(&contents, &theme.palette) {
+                diags.push(Diagnostic::warning(
+                    Some(option),
+                    format!(
+                        "option {} uses placeholder {}, which isn't a color in the palette",
+                        option, name
+                    ),
+                ));
+            }
+        }
+    }
+    for check in check_theme_contrast(&theme).iter().filter(|x| !x.passes()) {
+        diags.push(Diagnostic::warning(
+            Some(&check.source),
+            format!(
+                "{} ({}) on {} has a contrast ratio of {:.2}:1, below {}:1",
+                check.foreground,
+                check.fg.to_hex(),
+                check.bg.to_hex(),
+                check.ratio,
+                check.minimum
+            ),
+        ));
+    }
+    if theme.option_names.iter().any(|x| x == "poly") && exists("poly") {
+        info!("Checking polybar bars");
+        // Bars can be defined in the base file, so they're looked up in the composed config
+        match theme.compose_option("poly") {
+            Ok(contents) => {
+                for bar in select_bars(&contents, &theme.bars, &theme.order).missing {
+                    diags.push(Diagnostic::warning(
+                        Some("poly"),
+                        format!(
+                            "bar {} isn't defined in the polybar config and won't be started",
+                            bar
+                        ),
+                    ));
+                }
+            }
+            Err(e) => diags.push(Diagnostic::error(
+                Some("poly"),
+                format!("option poly can't be composed with its base files: {}", e),
+            )),
+        }
+    }
+    info!("Found {} problems in theme {}", diags.len(), theme_name);
+    Ok(diags)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::init_in;
+    use palette::Color;
+    use std::env;
+    use themes::new_theme_in;
+    /// Creates a theme in a home in the temp directory, with the given options and kv
+    fn test_theme(name: &str, options: &[&str], kv: &[(&str, &str)]) -> RavenPaths {
+        let home = env::temp_dir().join(format!(
+            "ravenlib-validate-{}-{}",
+            name,
+            ::std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&home);
+        let paths = RavenPaths::with_home(home.to_string_lossy().into_owned());
+        init_in(&paths).unwrap();
+        new_theme_in(&paths, "night").unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store.options = options.iter().map(|x| x.to_string()).collect();
+        for (k, v) in kv {
+            store.kv.insert(k.to_string(), Value::String(v.to_string()));
+        }
+        store.store_in(&paths).unwrap();
+        paths
+    }
+    /// The severity of the problem found with a subject, if any
+    fn severity(diags: &[Diagnostic], subject: &str) -> Option<Severity> {
+        diags
+            .iter()
+            .find(|x| x.subject.as_deref() == Some(subject))
+            .map(|x| x.severity.clone())
+    }
+    #[test]
+    fn finds_missing_files_and_unknown_names() {
+        let paths = test_theme(
+            "names",
+            &["wm", "xres", "nothing"],
+            &[
+                ("vscode", "Monokai"),
+                ("vim", "gruvbox"),
+                ("st_tmtheme", "sublt/missing.tmTheme"),
+            ],
+        );
+        fs::write(paths.theme_file("night", "xres"), "").unwrap();
+        fs::write(paths.theme_file("night", "rofi"), "").unwrap();
+        let diags = validate_theme_in(&paths, &LoaderRegistry::default(), "night").unwrap();
+        assert_eq!(severity(&diags, "wm"), Some(Severity::Error));
+        assert_eq!(severity(&diags, "xres"), None);
+        assert_eq!(severity(&diags, "nothing"), Some(Severity::Warning));
+        assert_eq!(severity(&diags, "rofi"), Some(Severity::Warning));
+        assert_eq!(severity(&diags, "vscode"), None);
+        assert_eq!(severity(&diags, "vim"), Some(Severity::Warning));
+        assert_eq!(severity(&diags, "st_tmtheme"), Some(Severity::Error));
+        assert!(validate_theme_in(&paths, &LoaderRegistry::default(), "day").is_err());
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+    #[test]
+    fn warns_about_unknown_placeholders() {
+        let paths = test_theme("placeholders", &["xres"], &[]);
+        fs::write(
+            paths.theme_file("night", "xres"),
+            "*.foreground: {{accent}}\n*.background: {{acent}}\n",
+        )
+        .unwrap();
+        let mut store = ThemeStore::load_in(&paths, "night").unwrap();
+        store
+            .palette
+            .insert(String::from("accent"), Color::new(0x80, 0x80, 0x80));
+        store.store_in(&paths).unwrap();
+        let diags = validate_theme_in(&paths, &LoaderRegistry::default(), "night").unwrap();
+        let unknown = diags
+            .iter()
+            .filter(|x| x.message.contains("placeholder"))
+            .collect::<Vec<_>>();
+        assert_eq!(unknown.len(), 1);
+        assert!(unknown[0].message.contains("acent"));
+        assert_eq!(unknown[0].severity, Severity::Warning);
+        fs::remove_dir_all(&paths.home).unwrap();
+    }
+}
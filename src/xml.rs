@@ -0,0 +1,194 @@
+use error::*;
+use std::cmp::Reverse;
+use text::indent_of;
+/// An element, with byte offsets into the source text
+struct Element {
+    name: String,
+    start: usize,
+    /// Offset just after the closing tag
+    end: usize,
+    /// Offsets of everything between the opening and closing tags
+    inner: (usize, usize),
+}
+fn error(message: &str, pos: usize) -> Error {
+    ErrorKind::InvalidXml(format!("{} at byte {}", message, pos)).into()
+}
+/// Offset of the > that ends the tag at the start of text, skipping over quoted attribute values
+fn tag_end(text: &str, pos: usize) -> Result<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Ok(i),
+            _ => {}
+        }
+    }
+    Err(error("unterminated tag", pos))
+}
+/// Elements directly inside the given range of text, skipping comments, declarations and CDATA
+fn elements(text: &str, from: usize, to: usize) -> Result<Vec<Element>> {
+    let mut found = Vec::new();
+    // Name, start and content start of each element that's still open
+    let mut open: Vec<(String, usize, usize)> = Vec::new();
+    let mut pos = from;
+    while let Some(i) = text[pos..to].find('<') {
+        let at = pos + i;
+        let rest = &text[at..to];
+        let skip = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            Some("]]>")
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(end) = skip {
+            pos = at
+                + rest
+                    .find(end)
+                    .ok_or_else(|| error("unterminated declaration", at))?
+                + end.len();
+            continue;
+        }
+        let close = at + tag_end(rest, at)?;
+        let tag = &text[at + 1..close];
+        pos = close + 1;
+        if tag.starts_with('/') {
+            let name = tag.strip_prefix('/').unwrap_or(tag).trim();
+            match open.pop() {
+                Some((ref open_name, start, inner)) if open_name == name => {
+                    if open.is_empty() {
+                        found.push(Element {
+                            name: name.to_string(),
+                            start,
+                            end: pos,
+                            inner: (inner, at),
+                        });
+                    }
+                }
+                _ => return Err(error(&format!("unexpected closing tag {}", name), at)),
+            }
+        } else {
+            let name = tag
+                .split(|x: char| x.is_whitespace() || x == '/')
+                .next()
+                .unwrap_or("")
+                .to_string();
+            if name.is_empty() {
+                return Err(error("tag without a name", at));
+            }
+            if tag.ends_with('/') {
+                if open.is_empty() {
+                    found.push(Element {
+                        name,
+                        start: at,
+                        end: pos,
+                        inner: (pos, pos),
+                    });
+                }
+            } else {
+                open.push((name, at, pos));
+            }
+        }
+    }
+    match open.pop() {
+        Some((name, start, _)) => Err(error(&format!("unclosed element {}", name), start)),
+        None => Ok(found),
+    }
+}
+/// Puts the top-level elements of the theme, like openbox's `<theme>`, `<desktops>` and `<margins>`, into the root element of the base document. Elements replace those of the same name in the base, and the rest go at the end of the root. The theme can either be a list of elements or a whole document with the same root.
+pub fn merge_xml(base: &str, theme: &str) -> Result<String> {
+    let root = match elements(base, 0, base.len())?.pop() {
+        Some(root) => root,
+        None => return Err(error("base has no root element", 0)),
+    };
+    if root.inner.0 == root.end {
+        // A self-closing root like <openbox_config/> gets a closing tag to put the elements before
+        let tag = base[root.start..root.end - 2].trim_end();
+        let expanded =
+            base[..root.start].to_string() + tag + ">\n</" + &root.name + ">" + &base[root.end..];
+        return merge_xml(&expanded, theme);
+    }
+    let mut theme_elements = elements(theme, 0, theme.len())?;
+    if theme_elements.is_empty() && !theme.trim().is_empty() {
+        return Err(error("theme has no elements", 0));
+    }
+    if theme_elements.len() == 1 && theme_elements[0].name == root.name {
+        let (from, to) = theme_elements[0].inner;
+        theme_elements = elements(theme, from, to)?;
+    }
+    let children = elements(base, root.inner.0, root.inner.1)?;
+    let indent = children
+        .last()
+        .map(|x| indent_of(base, x.start))
+        .unwrap_or("  ");
+    let mut replaced: Vec<(usize, usize, &str)> = Vec::new();
+    let mut added = String::new();
+    for (i, element) in theme_elements.iter().enumerate() {
+        // The last element of a name wins
+        if theme_elements[i + 1..]
+            .iter()
+            .any(|x| x.name == element.name)
+        {
+            continue;
+        }
+        let contents = &theme[element.start..element.end];
+        match children.iter().rev().find(|x| x.name == element.name) {
+            Some(child) => replaced.push((child.start, child.end, contents)),
+            None => {
+                info!("Adding element {} to {}", element.name, root.name);
+                added = added + indent + contents + "\n";
+            }
+        }
+    }
+    let close = root.inner.1;
+    let line_start = base[..close].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let mut merged = if base[line_start..close].trim().is_empty() {
+        base[..line_start].to_string() + &added + &base[line_start..]
+    } else if added.is_empty() {
+        base.to_string()
+    } else {
+        base[..close].to_string() + "\n" + &added + &base[close..]
+    };
+    replaced.sort_by_key(|x| Reverse(x.0));
+    for (start, end, contents) in replaced {
+        merged = merged[..start].to_string() + contents + &merged[end..];
+    }
+    Ok(merged)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const BASE: &str = "<?xml version=\"1.0\"?>\n<openbox_config>\n  <!-- <theme> -->\n  <theme>\n    <name>Old</name>\n  </theme>\n  <desktops number=\"4\"/>\n</openbox_config>\n";
+    #[test]
+    fn replaces_and_adds_elements() {
+        let merged = merge_xml(
+            BASE,
+            "<theme><name>New</name></theme>\n<margins><top>5</top></margins>",
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            "<?xml version=\"1.0\"?>\n<openbox_config>\n  <!-- <theme> -->\n  <theme><name>New</name></theme>\n  <desktops number=\"4\"/>\n  <margins><top>5</top></margins>\n</openbox_config>\n"
+        );
+    }
+    #[test]
+    fn accepts_whole_documents() {
+        let theme = "<openbox_config>\n  <desktops number=\"2\"/>\n</openbox_config>\n";
+        let merged = merge_xml(BASE, theme).unwrap();
+        assert_eq!(merged, BASE.replace("number=\"4\"", "number=\"2\""));
+    }
+    #[test]
+    fn expands_self_closing_roots() {
+        let merged = merge_xml("<openbox_config/>\n", "<theme/>").unwrap();
+        assert_eq!(merged, "<openbox_config>\n  <theme/>\n</openbox_config>\n");
+    }
+    #[test]
+    fn rejects_broken_documents() {
+        assert!(merge_xml(BASE, "just some text").is_err());
+        assert!(merge_xml(BASE, "<theme><name>New</theme>").is_err());
+        assert!(merge_xml("no root", "<theme/>").is_err());
+    }
+}